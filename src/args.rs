@@ -1,23 +1,104 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-use crate::{
+use eracer_config::{
+    patch::Signature,
     ratio::{Ratio, RatioStr},
     resolution::{Resolution, ResolutionStr},
+    store::schema::Kind,
 };
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
     #[clap(long, parse(try_from_str = parse_resolution), help="Set new resolution")]
     pub set_resolution: Option<Resolution>,
     #[clap(long, parse(try_from_str = parse_ratio), help="Set new aspect ratio")]
     pub set_aspect_ratio: Option<Ratio>,
     #[clap(long, help = "Reset aspect ratio to original")]
     pub reset_aspect_ratio: bool,
-    #[clap(long, help = "Override path to eracer.exe (gets from windows' registry if not set)")]
+    #[clap(long, help = "Override path to eracer.exe (gets from windows' registry if not set, required on other platforms)")]
     pub binary_path: Option<PathBuf>,
+    #[clap(long, help = "Wine prefix of the game with `user.reg` to read and change the settings in (`$WINEPREFIX` or `~/.wine` if not set and there is no windows' registry)")]
+    pub prefix: Option<PathBuf>,
+    #[clap(long, help = "Load additional known versions of eracer.exe from a TOML manifest")]
+    pub manifest: Option<PathBuf>,
+    #[clap(long, help = "Patch unknown version of eracer.exe if the aspect ratio is found by signature exactly once")]
+    pub allow_unknown: bool,
+    #[clap(long, parse(try_from_str = Signature::parse), help = "Byte pattern to find the aspect ratio in unknown version of eracer.exe, e.g. `?? 3A 46 71 3F`")]
+    pub signature: Option<Signature>,
+    #[clap(long, help = "Directory to keep a backup of eracer.exe in (next to eracer.exe if not set)")]
+    pub backup_dir: Option<PathBuf>,
+    #[clap(long, help = "Print all changes of the registry and eracer.exe without applying them")]
+    pub dry_run: bool,
+    #[clap(long, help = "Print the output of `--dry-run` as JSON")]
+    pub json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[clap(about = "Restore eracer.exe from the backup made before its first modification")]
+    Restore,
+    #[clap(about = "Export the aspect ratio patch of eracer.exe as IPS and BPS files")]
+    ExportPatch {
+        #[clap(parse(try_from_str = parse_ratio), help = "Aspect ratio to patch the original eracer.exe with")]
+        ratio: Ratio,
+        #[clap(long, help = "Path of the patch files without extension (`eracer_W-H` if not set)")]
+        output: Option<PathBuf>,
+    },
+    #[clap(about = "Explain why eracer.exe is not recognized as a known version")]
+    Diagnose {
+        #[clap(long, help = "Original copy of a known version to compare eracer.exe with byte by byte (the backup is used if it exists)")]
+        reference: Vec<PathBuf>,
+    },
+    #[clap(about = "Check which blocks of eracer.exe differ from the original release")]
+    Verify {
+        #[clap(long, help = "Original copy of a known version to compute hashes of blocks from if the manifest has none (the backup is used if it exists)")]
+        reference: Vec<PathBuf>,
+    },
+    #[clap(about = "Print the size and hashes of blocks of a known eracer.exe to put into a manifest")]
+    HashBlocks {
+        #[clap(long, help = "Size of a block in bytes (4096 if not set)")]
+        block_size: Option<usize>,
+    },
+    #[clap(about = "Find Wine prefixes (plain Wine, Steam/Proton, Lutris, Bottles) with e-Racer to pass via `--prefix`")]
+    Discover,
+    #[clap(about = "Export all values of the game in the registry as a .reg file")]
+    ExportReg {
+        #[clap(help = "Path of the .reg file")]
+        output: PathBuf,
+        #[clap(long, help = "Write a REGEDIT4 file (ASCII) instead of a Windows Registry Editor 5.00 one (UTF-16)")]
+        regedit4: bool,
+    },
+    #[clap(about = "Import the values of the game from a .reg file into the registry")]
+    ImportReg {
+        #[clap(help = "Path of the .reg file")]
+        file: PathBuf,
+    },
+    #[clap(about = "List all values of the game in the registry with their types")]
+    Values,
+    #[clap(about = "Print a value of the game in the registry")]
+    GetValue {
+        #[clap(help = "Name of the value, e.g. `PREFERRED WIDTH`")]
+        name: String,
+    },
+    #[clap(about = "Change or add a value of the game in the registry")]
+    SetValue {
+        #[clap(help = "Name of the value, e.g. `PREFERRED WIDTH`")]
+        name: String,
+        #[clap(help = "A string, a DWORD number (decimal or `0x` hex) or hex bytes (`01 FF`)")]
+        value: String,
+        #[clap(long = "type", help = "Type of the value: REG_SZ, REG_DWORD or REG_BINARY (the type of the existing value if not set)")]
+        kind: Option<Kind>,
+    },
+    #[clap(about = "Apply an IPS, BPS or VCDIFF (xdelta) patch to eracer.exe")]
+    ApplyPatch {
+        #[clap(help = "Path to the patch file")]
+        file: PathBuf,
+    },
 }
 
 fn parse_resolution(s: &str) -> Result<Resolution, &'static str> {
@@ -25,8 +106,8 @@ fn parse_resolution(s: &str) -> Result<Resolution, &'static str> {
 }
 
 fn parse_ratio(s: &str) -> Result<Ratio, String> {
-    RatioStr(s).try_into().map_err(|_| {
-        let ratios = Ratio::variants().skip(1).map(|r| r.to_string()).collect::<Vec<_>>();
-        format!("\r\nIncorrect or unsupported aspect ratio. Given: `{}`, but expected one of: \r\n\t * {}", s, ratios.join("\r\n\t * "))
+    RatioStr(s).try_into().map_err(|e| {
+        let ratios = Ratio::presets().map(|r| r.to_string()).collect::<Vec<_>>();
+        format!("\r\nIncorrect or unsupported aspect ratio ({}). Given: `{}`, but expected `W:H`, for example: \r\n\t * {}", e, s, ratios.join("\r\n\t * "))
     })
 }
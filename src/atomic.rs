@@ -0,0 +1,91 @@
+//! Files are never changed in place: the new content is written into a temporary file next to the
+//! original, read back and renamed over it
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::error_code::ErrorCode;
+
+/// A temporary file is placed in the same directory as `path` so it can be atomically renamed
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".tmp");
+    name.into()
+}
+
+/// Atomically replaces the content of `path` by `data`, keeping permissions of the file if it
+/// exists. Failures are reported as `error` (e.g. `ErrorCode::PatchFailed`) with the reason.
+pub(crate) fn replace(
+    path: &Path,
+    data: &[u8],
+    error: fn(String) -> ErrorCode,
+) -> Result<(), ErrorCode> {
+    let temp = temp_path(path);
+    let temp_str = temp.to_string_lossy();
+    let failed =
+        |step: &str, e: std::io::Error| error(format!("cannot {} `{}`: {}", step, temp_str, e));
+
+    let result = File::create(&temp)
+        .and_then(|mut file| file.write_all(data).and_then(|()| file.sync_all()))
+        .map_err(|e| failed("write into a temporary file", e))
+        .and_then(|()| std::fs::read(&temp).map_err(|e| failed("read a temporary file", e)))
+        .and_then(|written| match written == data {
+            true => Ok(()),
+            false => Err(error(format!(
+                "`{}` differs from the expected content after writing",
+                temp_str
+            ))),
+        })
+        .and_then(|()| match std::fs::metadata(path) {
+            Ok(metadata) => std::fs::set_permissions(&temp, metadata.permissions())
+                .map_err(|e| failed("set permissions of", e)),
+            Err(_) => Ok(()),
+        })
+        .and_then(|()| {
+            std::fs::rename(&temp, path).map_err(|e| {
+                error(format!(
+                    "cannot replace `{}` by `{}`: {}",
+                    path.to_string_lossy(),
+                    temp_str,
+                    e
+                ))
+            })
+        });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn atomic_replace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+
+        replace(&path, b"new", ErrorCode::PatchFailed).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        replace(&path, b"newer", ErrorCode::PatchFailed).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"newer");
+        assert!(std::fs::metadata(&path).unwrap().permissions().readonly());
+        assert!(!temp_path(&path).exists());
+
+        let missing = dir.path().join("missing").join("file");
+        assert!(matches!(
+            replace(&missing, b"new", ErrorCode::RegFile),
+            Err(ErrorCode::RegFile(_))
+        ));
+    }
+}
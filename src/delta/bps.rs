@@ -0,0 +1,321 @@
+//! BPS (beat): `BPS1`, sizes, actions, CRC32 of the source, the target and the patch itself
+
+use super::{diff_runs, max_target, Cursor};
+
+pub(super) const HEADER: &[u8; 4] = b"BPS1";
+
+const SOURCE_READ: u64 = 0;
+const TARGET_READ: u64 = 1;
+const SOURCE_COPY: u64 = 2;
+const TARGET_COPY: u64 = 3;
+
+/// Three CRC32: of the source, the target and the patch
+const FOOTER_LENGTH: usize = 12;
+
+/// Creates a BPS patch that turns `source` into `target` (of the same length)
+pub fn create(source: &[u8], target: &[u8]) -> Result<Vec<u8>, String> {
+    if source.len() != target.len() {
+        return Err("BPS patches are created for files of the same size only".to_owned());
+    }
+
+    let mut patch = HEADER.to_vec();
+    write_number(&mut patch, source.len() as u64);
+    write_number(&mut patch, target.len() as u64);
+    write_number(&mut patch, 0); // metadata
+
+    let mut position = 0;
+    for (offset, length) in diff_runs(source, target) {
+        if offset > position {
+            write_action(&mut patch, SOURCE_READ, offset - position);
+        }
+
+        write_action(&mut patch, TARGET_READ, length);
+        patch.extend_from_slice(&target[offset..offset + length]);
+        position = offset + length;
+    }
+
+    if target.len() > position {
+        write_action(&mut patch, SOURCE_READ, target.len() - position);
+    }
+
+    patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+    patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+    let checksum = crc32fast::hash(&patch);
+    patch.extend_from_slice(&checksum.to_le_bytes());
+
+    Ok(patch)
+}
+
+/// Applies a BPS patch. The patch is refused if `source` does not match its CRC32 stored in the
+/// patch.
+pub fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < HEADER.len() + FOOTER_LENGTH || !patch.starts_with(HEADER) {
+        return Err("not a BPS patch".to_owned());
+    }
+
+    let (body, footer) = patch.split_at(patch.len() - FOOTER_LENGTH);
+    let crc = |at: usize| {
+        u32::from_le_bytes([footer[at], footer[at + 1], footer[at + 2], footer[at + 3]])
+    };
+    if crc32fast::hash(&patch[..patch.len() - 4]) != crc(8) {
+        return Err("the BPS patch is damaged (checksum mismatch)".to_owned());
+    }
+
+    if crc32fast::hash(source) != crc(0) {
+        return Err("the BPS patch is made for another file (source checksum mismatch)".to_owned());
+    }
+
+    let mut cursor = Cursor::new(&body[HEADER.len()..]);
+    let source_size = read_size(&mut cursor)?;
+    let target_size = read_size(&mut cursor)?;
+    let metadata_size = read_size(&mut cursor)?;
+    cursor.take(metadata_size)?;
+
+    if source_size != source.len() {
+        return Err("the BPS patch is made for another file (source size mismatch)".to_owned());
+    }
+    if target_size > max_target(source) {
+        return Err(format!(
+            "the BPS patch makes a file of {} bytes, too big for the source",
+            target_size
+        ));
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+    let out_of_bounds = || "the BPS patch reads out of bounds".to_owned();
+
+    while !cursor.is_empty() {
+        let data = read_number(&mut cursor)?;
+        let length = usize::try_from(data >> 2)
+            .ok()
+            .and_then(|length| length.checked_add(1))
+            .filter(|&length| length <= target_size - target.len())
+            .ok_or("the BPS patch writes past the target size")?;
+
+        match data & 3 {
+            SOURCE_READ => {
+                let position = target.len();
+                let end = position.checked_add(length).ok_or_else(out_of_bounds)?;
+                let bytes = source.get(position..end).ok_or_else(out_of_bounds)?;
+                target.extend_from_slice(bytes);
+            }
+            TARGET_READ => target.extend_from_slice(cursor.take(length)?),
+            SOURCE_COPY => {
+                source_offset = relative(&mut cursor, source_offset)?;
+                let end = source_offset
+                    .checked_add(length)
+                    .ok_or_else(out_of_bounds)?;
+                target.extend_from_slice(source.get(source_offset..end).ok_or_else(out_of_bounds)?);
+                source_offset = end;
+            }
+            TARGET_COPY => {
+                // the copied bytes may overlap with the bytes being produced
+                target_offset = relative(&mut cursor, target_offset)?;
+                for _ in 0..length {
+                    let byte = *target.get(target_offset).ok_or_else(out_of_bounds)?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!("an action has two bits"),
+        }
+    }
+
+    if target.len() != target_size || crc32fast::hash(&target) != crc(4) {
+        return Err(
+            "the result of the BPS patch does not match (target checksum mismatch)".to_owned(),
+        );
+    }
+
+    Ok(target)
+}
+
+/// Applies a signed relative offset: the lowest bit is the sign
+fn relative(cursor: &mut Cursor, offset: usize) -> Result<usize, String> {
+    let data = read_number(cursor)?;
+    let result = usize::try_from(data >> 1)
+        .ok()
+        .and_then(|delta| match data & 1 {
+            0 => offset.checked_add(delta),
+            _ => offset.checked_sub(delta),
+        });
+
+    result.ok_or_else(|| "the BPS patch reads out of bounds".to_owned())
+}
+
+fn read_size(cursor: &mut Cursor) -> Result<usize, String> {
+    usize::try_from(read_number(cursor)?)
+        .map_err(|_| "a size in the BPS patch is too big".to_owned())
+}
+
+fn read_number(cursor: &mut Cursor) -> Result<u64, String> {
+    let mut data: u64 = 0;
+    let mut shift: u64 = 1;
+
+    loop {
+        let x = cursor.byte()?;
+        data = (x as u64 & 0x7F)
+            .checked_mul(shift)
+            .and_then(|x| data.checked_add(x))
+            .ok_or("a number in the BPS patch is too big")?;
+        if x & 0x80 != 0 {
+            break;
+        }
+
+        shift = shift
+            .checked_shl(7)
+            .ok_or("a number in the BPS patch is too big")?;
+        data = data
+            .checked_add(shift)
+            .ok_or("a number in the BPS patch is too big")?;
+    }
+
+    Ok(data)
+}
+
+fn write_action(patch: &mut Vec<u8>, action: u64, length: usize) {
+    write_number(patch, ((length as u64 - 1) << 2) | action);
+}
+
+/// Variable-length number as defined by beat
+fn write_number(patch: &mut Vec<u8>, mut number: u64) {
+    loop {
+        let x = (number & 0x7F) as u8;
+        number >>= 7;
+        if number == 0 {
+            patch.push(0x80 | x);
+            break;
+        }
+
+        patch.push(x);
+        number -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bps_number() {
+        let mut buffer = vec![];
+        write_number(&mut buffer, 0);
+        write_number(&mut buffer, 0x7F);
+        write_number(&mut buffer, 0x80);
+        write_number(&mut buffer, 0x4080);
+        assert_eq!(buffer, [0x80, 0xFF, 0x00, 0x80, 0x00, 0x00, 0x80]);
+
+        let mut cursor = Cursor::new(&buffer);
+        for number in [0, 0x7F, 0x80, 0x4080] {
+            assert_eq!(read_number(&mut cursor).unwrap(), number);
+        }
+    }
+
+    #[test]
+    fn bps_apply() {
+        let source = b"0123456789".to_vec();
+        let mut target = source.clone();
+        target[2..4].copy_from_slice(b"ab");
+
+        let patch = create(&source, &target).unwrap();
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+
+        let mut other = source.clone();
+        other[0] = b'X';
+        assert!(apply(&other, &patch)
+            .unwrap_err()
+            .contains("source checksum"));
+
+        let mut damaged = patch.clone();
+        damaged[8] ^= 0xFF;
+        assert!(apply(&source, &damaged).unwrap_err().contains("damaged"));
+    }
+
+    #[test]
+    fn bps_apply_copies() {
+        let source = b"abcdef".to_vec();
+        let target = b"defabcxyxyxy".to_vec();
+
+        let mut patch = HEADER.to_vec();
+        write_number(&mut patch, 6);
+        write_number(&mut patch, 12);
+        write_number(&mut patch, 0);
+        write_action(&mut patch, SOURCE_COPY, 3);
+        write_number(&mut patch, 3 << 1); // +3
+        write_action(&mut patch, SOURCE_COPY, 3);
+        write_number(&mut patch, (6 << 1) | 1); // -6
+        write_action(&mut patch, TARGET_READ, 2);
+        patch.extend_from_slice(b"xy");
+        write_action(&mut patch, TARGET_COPY, 4);
+        write_number(&mut patch, 6 << 1); // +6, overlapped
+
+        assert_eq!(
+            apply(&source, &footer(patch, &source, &target)).unwrap(),
+            target
+        );
+    }
+
+    #[test]
+    fn bps_apply_bounds() {
+        let source = b"abcdef".to_vec();
+        let header = |target_size: u64| {
+            let mut patch = HEADER.to_vec();
+            write_number(&mut patch, 6);
+            write_number(&mut patch, target_size);
+            write_number(&mut patch, 0);
+            patch
+        };
+
+        let patch = footer(header(1 << 40), &source, b"");
+        assert!(apply(&source, &patch).unwrap_err().contains("too big"));
+
+        // a copy far longer than the declared target is refused before it runs
+        let mut patch = header(8);
+        write_action(&mut patch, TARGET_READ, 1);
+        patch.push(b'x');
+        write_action(&mut patch, TARGET_COPY, 1 << 40);
+        write_number(&mut patch, 0);
+        let patch = footer(patch, &source, b"");
+        assert!(apply(&source, &patch)
+            .unwrap_err()
+            .contains("past the target"));
+
+        let mut patch = header(8);
+        write_action(&mut patch, SOURCE_COPY, 2);
+        write_number(&mut patch, u64::MAX - 1);
+        let patch = footer(patch, &source, b"");
+        assert!(apply(&source, &patch)
+            .unwrap_err()
+            .contains("out of bounds"));
+    }
+
+    /// Appends the checksums to an action list made by hand
+    fn footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+        let checksum = crc32fast::hash(&patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn bps_create() {
+        let source = vec![0; 0x20];
+        let mut target = source.clone();
+        target[0x10..0x13].copy_from_slice(&[0x3A, 0x46, 0x71]);
+
+        let patch = create(&source, &target).unwrap();
+        assert_eq!(patch[..4], *b"BPS1");
+        assert_eq!(patch[4..7], [0xA0, 0xA0, 0x80]);
+        // SourceRead 16, TargetRead 3, SourceRead 13
+        assert_eq!(patch[7..12], [0xBC, 0x89, 0x3A, 0x46, 0x71]);
+        assert_eq!(patch[12], 0xB0);
+
+        let crc = |at: usize| u32::from_le_bytes(patch[at..at + 4].try_into().unwrap());
+        assert_eq!(crc(13), crc32fast::hash(&source));
+        assert_eq!(crc(17), crc32fast::hash(&target));
+        assert_eq!(crc(21), crc32fast::hash(&patch[..21]));
+        assert_eq!(patch.len(), 25);
+    }
+}
@@ -0,0 +1,137 @@
+//! IPS: `PATCH`, records of `offset: u24 BE, size: u16 BE, data`, `EOF`
+
+use super::{diff_runs, Cursor};
+
+pub(super) const HEADER: &[u8; 5] = b"PATCH";
+const FOOTER: &[u8; 3] = b"EOF";
+const MAX_OFFSET: usize = 0xFF_FFFF;
+const MAX_RECORD: usize = 0xFFFF;
+
+/// An offset that looks like `EOF` must not start a record
+const EOF_OFFSET: usize = 0x45_4F46;
+
+/// Creates an IPS patch that turns `source` into `target` (of the same length)
+pub fn create(source: &[u8], target: &[u8]) -> Result<Vec<u8>, String> {
+    if source.len() != target.len() {
+        return Err("IPS patches are created for files of the same size only".to_owned());
+    }
+
+    let mut patch = HEADER.to_vec();
+    for (mut offset, mut length) in diff_runs(source, target) {
+        if offset == EOF_OFFSET {
+            offset -= 1;
+            length += 1;
+        }
+
+        while length > 0 {
+            if offset + length > MAX_OFFSET {
+                return Err(format!("offset {:#X} does not fit into IPS", offset));
+            }
+
+            let size = length.min(MAX_RECORD);
+            patch.extend_from_slice(&(offset as u32).to_be_bytes()[1..]);
+            patch.extend_from_slice(&(size as u16).to_be_bytes());
+            patch.extend_from_slice(&target[offset..offset + size]);
+
+            offset += size;
+            length -= size;
+        }
+    }
+    patch.extend_from_slice(FOOTER);
+
+    Ok(patch)
+}
+
+/// Applies an IPS patch (including RLE records and the truncation extension). IPS has no
+/// checksums, so the source cannot be validated.
+pub fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut cursor = Cursor::new(patch);
+    if cursor.take(HEADER.len())? != HEADER {
+        return Err("not an IPS patch".to_owned());
+    }
+
+    let mut target = source.to_vec();
+    loop {
+        let offset = cursor.take(3)?;
+        if offset == FOOTER {
+            match cursor.len() {
+                0 => break,
+                3 => {
+                    target.truncate(read_u24(cursor.take(3)?));
+                    break;
+                }
+                _ => return Err("unexpected data after `EOF` of the IPS patch".to_owned()),
+            }
+        }
+
+        let offset = read_u24(offset);
+        let size = u16::from_be_bytes([cursor.byte()?, cursor.byte()?]) as usize;
+        let data = match size {
+            0 => {
+                let size = u16::from_be_bytes([cursor.byte()?, cursor.byte()?]) as usize;
+                vec![cursor.byte()?; size]
+            }
+            _ => cursor.take(size)?.to_vec(),
+        };
+
+        if target.len() < offset + data.len() {
+            target.resize(offset + data.len(), 0);
+        }
+        target[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    Ok(target)
+}
+
+fn read_u24(bytes: &[u8]) -> usize {
+    u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ips_create() {
+        let source = vec![0; 0x20];
+        let mut target = source.clone();
+        target[0x10..0x13].copy_from_slice(&[0x3A, 0x46, 0x71]);
+
+        let patch = create(&source, &target).unwrap();
+        assert_eq!(patch, b"PATCH\x00\x00\x10\x00\x03\x3A\x46\x71EOF".to_vec());
+
+        assert_eq!(create(&source, &source).unwrap(), b"PATCHEOF".to_vec());
+        assert!(create(&source, &target[1..]).is_err());
+    }
+
+    #[test]
+    fn ips_apply() {
+        let source = b"0123456789".to_vec();
+        let mut target = source.clone();
+        target[2..4].copy_from_slice(b"ab");
+        target[9] = b'z';
+
+        let patch = create(&source, &target).unwrap();
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+
+        // RLE record, a record beyond the end and truncation
+        let patch = b"PATCH\x00\x00\x01\x00\x00\x00\x03x\x00\x00\x0C\x00\x01yEOF\x00\x00\x0D";
+        assert_eq!(apply(&source, patch).unwrap(), b"0xxx456789\0\0y".to_vec());
+
+        assert!(apply(&source, b"PATCH\x00\x00\x01\x00\x05ab").is_err());
+        assert!(apply(&source, b"PATCHEOFjunk").is_err());
+    }
+
+    #[test]
+    fn ips_eof_offset() {
+        let source = vec![0; EOF_OFFSET + 2];
+        let mut target = source.clone();
+        target[EOF_OFFSET] = 1;
+
+        let patch = create(&source, &target).unwrap();
+        assert_eq!(
+            patch[5..13],
+            [0x45, 0x4F, 0x45, 0x00, 0x02, 0x00, 0x01, b'E']
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Binary patch formats understood by common ROM/exe patchers
+
+pub mod bps;
+pub mod ips;
+pub mod vcdiff;
+
+/// Applies a patch of any supported format (detected by its magic bytes) to `source`
+pub fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.starts_with(ips::HEADER) {
+        ips::apply(source, patch)
+    } else if patch.starts_with(bps::HEADER) {
+        bps::apply(source, patch)
+    } else if patch.starts_with(vcdiff::MAGIC) {
+        vcdiff::apply(source, patch)
+    } else {
+        Err("unknown format of the patch (IPS, BPS and VCDIFF/xdelta are supported)".to_owned())
+    }
+}
+
+/// How much bigger than the source a patched file may be. Sizes in a patch are not trusted: a
+/// bigger target or window is refused before anything is allocated.
+const MAX_GROWTH: usize = 64 << 20;
+
+/// The biggest target a patch of `source` may produce
+fn max_target(source: &[u8]) -> usize {
+    source.len().saturating_add(MAX_GROWTH)
+}
+
+/// Reads a patch from the beginning to the end
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if count > self.data.len() {
+            return Err("unexpected end of the patch".to_owned());
+        }
+
+        let (taken, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+}
+
+/// Ranges `(offset, length)` where `source` and `target` of the same length differ
+pub(crate) fn diff_runs(source: &[u8], target: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs = vec![];
+    let mut start = None;
+
+    for (offset, (s, t)) in source.iter().zip(target).enumerate() {
+        match (s != t, start) {
+            (true, None) => start = Some(offset),
+            (false, Some(begin)) => {
+                runs.push((begin, offset - begin));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(begin) = start {
+        runs.push((begin, source.len() - begin));
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delta_apply_detects_format() {
+        let source = b"source file".to_vec();
+        let target = b"target file".to_vec();
+
+        let ips = ips::create(&source, &target).unwrap();
+        let bps = bps::create(&source, &target).unwrap();
+        assert_eq!(apply(&source, &ips).unwrap(), target);
+        assert_eq!(apply(&source, &bps).unwrap(), target);
+        assert!(apply(&source, b"unknown").is_err());
+    }
+
+    #[test]
+    fn delta_diff_runs() {
+        assert_eq!(diff_runs(b"abcdef", b"abcdef"), vec![]);
+        assert_eq!(diff_runs(b"abcdef", b"aXYdeZ"), vec![(1, 2), (5, 1)]);
+        assert_eq!(diff_runs(b"abcdef", b"Xbcdef"), vec![(0, 1)]);
+    }
+}
@@ -0,0 +1,401 @@
+//! Decoder of VCDIFF (RFC 3284), the format of xdelta3 patches. Secondary compression and
+//! custom code tables are not supported (`xdelta3 -S none` makes a compatible patch).
+
+use super::{max_target, Cursor};
+
+pub(super) const MAGIC: &[u8; 4] = &[0xD6, 0xC3, 0xC4, 0x00];
+
+const VCD_DECOMPRESS: u8 = 0x01;
+const VCD_CODETABLE: u8 = 0x02;
+/// xdelta3 extension: application-defined header
+const VCD_APPHEADER: u8 = 0x04;
+
+const VCD_SOURCE: u8 = 0x01;
+const VCD_TARGET: u8 = 0x02;
+/// xdelta3 extension: Adler-32 of the target window
+const VCD_ADLER32: u8 = 0x04;
+
+const NOOP: u8 = 0;
+const ADD: u8 = 1;
+const RUN: u8 = 2;
+const COPY: u8 = 3;
+
+const NEAR_SIZE: usize = 4;
+const SAME_SIZE: usize = 3;
+
+#[derive(Debug, Clone, Copy)]
+struct Instruction {
+    kind: u8,
+    size: usize,
+    mode: u8,
+}
+
+const EMPTY: Instruction = Instruction {
+    kind: NOOP,
+    size: 0,
+    mode: 0,
+};
+
+/// Applies a VCDIFF patch. VCDIFF has no checksum of the source, so a wrong source is detected
+/// by the checksum of the target (if the patch has one).
+pub fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut cursor = Cursor::new(patch);
+    if cursor.take(MAGIC.len())? != MAGIC {
+        return Err("not a VCDIFF patch".to_owned());
+    }
+
+    let indicator = cursor.byte()?;
+    if indicator & VCD_DECOMPRESS != 0 {
+        return Err("secondary compression of VCDIFF is not supported".to_owned());
+    }
+    if indicator & VCD_CODETABLE != 0 {
+        return Err("custom code table of VCDIFF is not supported".to_owned());
+    }
+    if indicator & VCD_APPHEADER != 0 {
+        let length = read_number(&mut cursor)?;
+        cursor.take(length)?;
+    }
+
+    let table = code_table();
+    let mut target = Vec::new();
+    while !cursor.is_empty() {
+        window(&mut cursor, &table, source, &mut target)?;
+    }
+
+    Ok(target)
+}
+
+fn window(
+    cursor: &mut Cursor,
+    table: &[[Instruction; 2]; 256],
+    source: &[u8],
+    target: &mut Vec<u8>,
+) -> Result<(), String> {
+    let out_of_bounds = || "the VCDIFF patch reads out of bounds".to_owned();
+
+    let indicator = cursor.byte()?;
+    let segment = match indicator & (VCD_SOURCE | VCD_TARGET) {
+        0 => Vec::new(),
+        kind if kind == VCD_SOURCE | VCD_TARGET => {
+            return Err("incorrect window of the VCDIFF patch".to_owned())
+        }
+        kind => {
+            let length = read_number(cursor)?;
+            let position = read_number(cursor)?;
+            let from = if kind == VCD_SOURCE {
+                source
+            } else {
+                &target[..]
+            };
+            let end = position.checked_add(length).ok_or_else(out_of_bounds)?;
+            from.get(position..end)
+                .ok_or_else(|| {
+                    "the VCDIFF patch is made for another file (source is too short)".to_owned()
+                })?
+                .to_vec()
+        }
+    };
+
+    let length = read_number(cursor)?;
+    let mut delta = Cursor::new(cursor.take(length)?);
+    let window_length = read_number(&mut delta)?;
+    if window_length > max_target(source).saturating_sub(target.len()) {
+        return Err(format!(
+            "the VCDIFF patch makes a window of {} bytes, too big for the source",
+            window_length
+        ));
+    }
+    if delta.byte()? != 0 {
+        return Err("compressed sections of VCDIFF are not supported".to_owned());
+    }
+
+    let data_length = read_number(&mut delta)?;
+    let inst_length = read_number(&mut delta)?;
+    let addr_length = read_number(&mut delta)?;
+    let checksum = match indicator & VCD_ADLER32 {
+        0 => None,
+        _ => {
+            let bytes = delta.take(4)?;
+            Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+    };
+
+    let mut data = Cursor::new(delta.take(data_length)?);
+    let mut inst = Cursor::new(delta.take(inst_length)?);
+    let mut addr = Cursor::new(delta.take(addr_length)?);
+
+    // The segment followed by the target window, COPY addresses both
+    let mut buffer = segment;
+    let start = buffer.len();
+    let mut cache = AddressCache::default();
+
+    while !inst.is_empty() {
+        for instruction in table[inst.byte()? as usize] {
+            let size = match (instruction.kind, instruction.size) {
+                (NOOP, _) => continue,
+                (_, 0) => read_number(&mut inst)?,
+                (_, size) => size,
+            };
+
+            // sizes are checked against the declared window before the buffer grows
+            if size > window_length - (buffer.len() - start) {
+                return Err("the VCDIFF patch writes past the window length".to_owned());
+            }
+
+            match instruction.kind {
+                ADD => buffer.extend_from_slice(data.take(size)?),
+                RUN => {
+                    let byte = data.byte()?;
+                    buffer.resize(buffer.len() + size, byte);
+                }
+                _ => {
+                    let here = buffer.len();
+                    let address = cache.decode(&mut addr, here, instruction.mode)?;
+                    if address >= here {
+                        return Err(out_of_bounds());
+                    }
+
+                    // the copied bytes may overlap with the bytes being produced
+                    let end = address.checked_add(size).ok_or_else(out_of_bounds)?;
+                    for i in address..end {
+                        buffer.push(buffer[i]);
+                    }
+                }
+            }
+        }
+    }
+
+    let window = &buffer[start..];
+    if window.len() != window_length {
+        return Err("incorrect window length of the VCDIFF patch".to_owned());
+    }
+
+    if checksum.is_some_and(|checksum| checksum != adler32(window)) {
+        return Err(
+            "the result of the VCDIFF patch does not match (the patch is made for another file)"
+                .to_owned(),
+        );
+    }
+
+    target.extend_from_slice(window);
+    Ok(())
+}
+
+#[derive(Default)]
+struct AddressCache {
+    near: [usize; NEAR_SIZE],
+    next: usize,
+    same: Vec<usize>,
+}
+
+impl AddressCache {
+    fn decode(&mut self, addr: &mut Cursor, here: usize, mode: u8) -> Result<usize, String> {
+        let overflow = || "an address in the VCDIFF patch is out of bounds".to_owned();
+
+        let address = match mode as usize {
+            0 => read_number(addr)?,
+            1 => here.checked_sub(read_number(addr)?).ok_or_else(overflow)?,
+            m if m < 2 + NEAR_SIZE => self.near[m - 2]
+                .checked_add(read_number(addr)?)
+                .ok_or_else(overflow)?,
+            m => {
+                let index = (m - 2 - NEAR_SIZE) * 256 + addr.byte()? as usize;
+                self.same.get(index).copied().unwrap_or(0)
+            }
+        };
+
+        self.near[self.next] = address;
+        self.next = (self.next + 1) % NEAR_SIZE;
+        if self.same.is_empty() {
+            self.same = vec![0; SAME_SIZE * 256];
+        }
+        self.same[address % (SAME_SIZE * 256)] = address;
+
+        Ok(address)
+    }
+}
+
+/// The default code table (RFC 3284, section 5.6)
+fn code_table() -> [[Instruction; 2]; 256] {
+    let single = |kind, size, mode| [Instruction { kind, size, mode }, EMPTY];
+    let mut table = Vec::with_capacity(256);
+
+    table.push(single(RUN, 0, 0));
+    for size in 0..18 {
+        table.push(single(ADD, size, 0));
+    }
+
+    for mode in 0..9 {
+        table.push(single(COPY, 0, mode));
+        for size in 4..19 {
+            table.push(single(COPY, size, mode));
+        }
+    }
+
+    for mode in 0..9 {
+        let copy_sizes = if mode < 6 { 4..7 } else { 4..5 };
+        for add_size in 1..5 {
+            for copy_size in copy_sizes.clone() {
+                table.push([
+                    Instruction {
+                        kind: ADD,
+                        size: add_size,
+                        mode: 0,
+                    },
+                    Instruction {
+                        kind: COPY,
+                        size: copy_size,
+                        mode,
+                    },
+                ]);
+            }
+        }
+    }
+
+    for mode in 0..9 {
+        table.push([
+            Instruction {
+                kind: COPY,
+                size: 4,
+                mode,
+            },
+            Instruction {
+                kind: ADD,
+                size: 1,
+                mode: 0,
+            },
+        ]);
+    }
+
+    table
+        .try_into()
+        .expect("the default code table has 256 entries")
+}
+
+/// Big-endian base-128 number
+fn read_number(cursor: &mut Cursor) -> Result<usize, String> {
+    let mut number: usize = 0;
+
+    loop {
+        let byte = cursor.byte()?;
+        number = number
+            .checked_mul(128)
+            .ok_or("a number in the VCDIFF patch is too big")?
+            | (byte & 0x7F) as usize;
+        if byte & 0x80 == 0 {
+            return Ok(number);
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A single window with `VCD_SOURCE` segment of the whole `source`
+    fn patch(source: &[u8], target: &[u8], data: &[u8], inst: &[u8], addr: &[u8]) -> Vec<u8> {
+        let mut delta = vec![target.len() as u8, 0];
+        delta.extend_from_slice(&[data.len() as u8, inst.len() as u8, addr.len() as u8]);
+        delta.extend_from_slice(&adler32(target).to_be_bytes());
+        delta.extend_from_slice(data);
+        delta.extend_from_slice(inst);
+        delta.extend_from_slice(addr);
+
+        let mut patch = MAGIC.to_vec();
+        patch.extend_from_slice(&[0, VCD_SOURCE | VCD_ADLER32, source.len() as u8, 0]);
+        patch.push(delta.len() as u8);
+        patch.extend_from_slice(&delta);
+        patch
+    }
+
+    #[test]
+    fn vcdiff_code_table() {
+        let table = code_table();
+        assert_eq!(table[0][0].kind, RUN);
+        assert_eq!((table[18][0].kind, table[18][0].size), (ADD, 17));
+        assert_eq!((table[19][0].kind, table[19][0].size), (COPY, 0));
+        assert_eq!((table[162][0].size, table[162][0].mode), (18, 8));
+        assert_eq!((table[163][0].size, table[163][1].size), (1, 4));
+        assert_eq!((table[246][0].size, table[246][1].mode), (4, 8));
+        assert_eq!((table[255][0].mode, table[255][1].kind), (8, ADD));
+    }
+
+    #[test]
+    fn vcdiff_apply() {
+        let source = b"0123456789".to_vec();
+        let target = b"0123abxxxx67898989".to_vec();
+
+        // COPY 4 from 0 (SELF), ADD 2, RUN 4, COPY 4 from 6 (SELF), COPY 4 from 2 back (HERE)
+        let inst = [19 + 1, 1 + 2, 0, 4, 19 + 1, 19 + 16 + 1];
+        let patch = patch(&source, &target, b"abx", &inst, &[0, 6, 2]);
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+
+        let mut other = source.clone();
+        other[0] = b'X';
+        assert!(apply(&other, &patch).unwrap_err().contains("another file"));
+        assert!(apply(b"short", &patch).is_err());
+    }
+
+    #[test]
+    fn vcdiff_apply_bounds() {
+        let source = b"0123456789".to_vec();
+
+        // a RUN of 127 bytes in a window of 4
+        let patch = patch(&source, b"xxxx", b"x", &[0, 0x7F], &[]);
+        assert!(apply(&source, &patch)
+            .unwrap_err()
+            .contains("past the window"));
+
+        // a window of 1 GiB is refused before anything is allocated
+        let mut patch = MAGIC.to_vec();
+        patch.extend_from_slice(&[0, 0, 9]);
+        patch.extend_from_slice(&[0x84, 0x80, 0x80, 0x80, 0, 0, 0, 0, 0]);
+        assert!(apply(&source, &patch).unwrap_err().contains("too big"));
+
+        // a segment at `usize::MAX`
+        let mut patch = MAGIC.to_vec();
+        patch.extend_from_slice(&[0, VCD_SOURCE, 2, 0x81]);
+        patch.extend_from_slice(&[0xFF; 8]);
+        patch.push(0x7F);
+        assert!(apply(&source, &patch)
+            .unwrap_err()
+            .contains("out of bounds"));
+    }
+
+    #[test]
+    fn vcdiff_unsupported() {
+        let mut patch = MAGIC.to_vec();
+        patch.push(VCD_DECOMPRESS);
+        assert!(apply(b"", &patch).is_err());
+    }
+
+    #[test]
+    fn vcdiff_number() {
+        let mut cursor = Cursor::new(&[0x7F, 0x81, 0x00, 0xBA, 0xEF, 0x9A, 0x15]);
+        assert_eq!(read_number(&mut cursor).unwrap(), 0x7F);
+        assert_eq!(read_number(&mut cursor).unwrap(), 0x80);
+        assert_eq!(read_number(&mut cursor).unwrap(), 123456789);
+    }
+
+    #[test]
+    fn vcdiff_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt::{Display, Formatter, Result};
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ErrorCode {
     RegistryEntryNotFound,
     RegistryInstallDirNotFound,
@@ -15,6 +16,25 @@ pub enum ErrorCode {
     IncorrectHashOfBinary(String),
     IO(std::io::Error),
     UnknownRatio,
+    IncorrectManifest(String),
+    SignatureNotFound(String),
+    SignatureAmbiguous(String, Vec<usize>),
+    BackupNotFound(String),
+    BackupCorrupted(String),
+    RestoredHashMismatch(String),
+    PatchFailed(String),
+    VerificationFailed(String, String),
+    PatchFile(String),
+    BlocksUnknown(String),
+    BlocksMismatch(String, usize),
+    RegistryUnsupported,
+    RegistryValueChange(String),
+    WinePrefixNotFound(String),
+    RegFile(String),
+    RegistryValueNotFound(String),
+    RegistryValueIncorrect(String, String),
+    BackupMismatch(String, String),
+    WinePrefixWrite(String),
 }
 
 impl Display for ErrorCode {
@@ -32,9 +52,37 @@ impl Display for ErrorCode {
             RegistryResolutionWidthChange => write!(f, r"Cannot change the entry `PREFERRED WIDTH` in windows registry."),
             RegistryResolutionHeightChange => write!(f, r"Cannot change the entry `PREFERRED HEIGHT` in windows registry."),
             NotFoundBinary(s) => write!(f, "Not found a binary file: `{}`. You can try to specify a path to `eracer.exe` by passthrough key `--binary-path`.", s),
-            IncorrectHashOfBinary(s) => write!(f, "Incorrect hash of a binary `{}`. Is path to `eracer.exe` correct?", s),
+            IncorrectHashOfBinary(s) => write!(f, "Incorrect hash of a binary `{}`. Is path to `eracer.exe` correct? Run `eracer-config diagnose` to find out why it is not recognized.", s),
             IO(e) => write!(f, "IO error: {:?}", e),
             UnknownRatio => write!(f, "Unknown aspect ratio"),
+            IncorrectManifest(s) => write!(f, "Incorrect manifest of known versions: {}", s),
+            SignatureNotFound(s) => write!(f, "Cannot find the aspect ratio in an unknown binary `{}`. You can try another pattern by passthrough key `--signature`.", s),
+            SignatureAmbiguous(s, offsets) => write!(f, "The aspect ratio is found in an unknown binary `{}` more than once (at {:#010X?}). You can try more specific pattern by passthrough key `--signature`.", s, offsets),
+            BackupNotFound(s) => write!(f, "Not found a backup: `{}`. You can try to specify a directory with backups by passthrough key `--backup-dir`.", s),
+            BackupCorrupted(s) => write!(f, "A backup `{}` does not match its recorded hash.", s),
+            RestoredHashMismatch(s) => write!(f, "A binary `{}` does not match the original release after restoring.", s),
+            PatchFailed(s) => write!(f, "Patching is aborted, the binary is left untouched: {}.", s),
+            VerificationFailed(s, reason) => write!(f, "Verification of the binary `{}` failed: {}. You can restore it by running `eracer-config restore`.", s, reason),
+            PatchFile(s) => write!(f, "Patch file error: {}.", s),
+            BlocksUnknown(s) => write!(f, "Cannot verify a binary `{}`: no known version has hashes of blocks in the manifest and no backup or `--reference` copy of its version is found. Pass an original copy via `--reference`, or run `eracer-config hash-blocks` on a pristine release and pass its entry via `--manifest`.", s),
+            BlocksMismatch(s, count) => write!(f, "A binary `{}` differs from the original release in {} blocks. You can restore it by running `eracer-config restore`.", s, count),
+            RegistryUnsupported => write!(f, r"Windows registry is not available on this platform (or the utility is built without the `windows-registry` feature). Pass the Wine prefix of the game via `--prefix` or the path to `eracer.exe` via `--binary-path` (the resolution cannot be changed then)."),
+            RegistryValueChange(s) => write!(f, r"Cannot change the entry `{}` in the registry.", s),
+            WinePrefixNotFound(s) => write!(f, r"Cannot read `user.reg` of the Wine prefix `{}`. Run `eracer.exe` in this prefix first or check the path.", s),
+            RegFile(s) => write!(f, "Registry file error: {}.", s),
+            RegistryValueNotFound(s) => write!(f, r"Cannot find the entry `{}` in the registry. Run `eracer-config values` to list the existing ones.", s),
+            RegistryValueIncorrect(s, reason) => write!(f, r"Incorrect value of the entry `{}`: {}.", s, reason),
+            BackupMismatch(s, reason) => write!(f, "A backup `{}` does not belong to the binary: {}. Move it away to make a new one, or pass another directory via `--backup-dir`.", s, reason),
+            WinePrefixWrite(s) => write!(f, r"Cannot write `user.reg` of the Wine prefix: {}. Make sure Wine is not running and the file is writable.", s),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCode {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ErrorCode::IO(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -57,6 +105,25 @@ impl From<&ErrorCode> for i32 {
             IncorrectHashOfBinary(_) => 12,
             IO(_) => 13,
             UnknownRatio => 14,
+            IncorrectManifest(_) => 15,
+            SignatureNotFound(_) => 16,
+            SignatureAmbiguous(_, _) => 17,
+            BackupNotFound(_) => 18,
+            BackupCorrupted(_) => 19,
+            RestoredHashMismatch(_) => 20,
+            PatchFailed(_) => 21,
+            VerificationFailed(_, _) => 22,
+            PatchFile(_) => 23,
+            BlocksUnknown(_) => 24,
+            BlocksMismatch(_, _) => 25,
+            RegistryUnsupported => 26,
+            RegistryValueChange(_) => 27,
+            WinePrefixNotFound(_) => 28,
+            RegFile(_) => 29,
+            RegistryValueNotFound(_) => 30,
+            RegistryValueIncorrect(_, _) => 31,
+            BackupMismatch(_, _) => 32,
+            WinePrefixWrite(_) => 33,
         }
     }
 }
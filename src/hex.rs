@@ -0,0 +1,20 @@
+/// Parses hex bytes, whitespaces between them are ignored: `"3A 46 71"`, `"3a4671"`
+pub fn parse(s: &str) -> Result<Vec<u8>, String> {
+    let digits = s
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("`{}` is not a hex string", s))?;
+
+    if digits.len() % 2 != 0 {
+        return Err(format!("`{}` has odd number of hex digits", s));
+    }
+
+    Ok(digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect())
+}
+
+/// Lowercase hex string without separators, as printed by `sha256sum`
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,40 @@
+//! Support of high resolutions and widescreen for e-Racer.
+//!
+//! The crate detects the version of `eracer.exe`, reads and patches its aspect ratio (and other
+//! patch sites described by a [`Manifest`]) and accesses the settings of the game stored in the
+//! registry (see [`Store`] for its backends). The `eracer-config` utility is a thin command line
+//! wrapper around it.
+//!
+//! ```no_run
+//! use eracer_config::{Binary, Manifest, Ratio};
+//!
+//! let manifest = Manifest::embedded();
+//! let mut binary = Binary::new("eracer.exe".as_ref(), &manifest)?;
+//! println!("{} at {:?}", binary.patch().name(), binary.aspect());
+//!
+//! binary.set_ratio(Ratio::new(16, 9)?)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#![allow(clippy::try_err)]
+
+pub(crate) mod atomic;
+pub(crate) mod delta;
+pub mod error_code;
+pub(crate) mod hex;
+pub mod patch;
+pub(crate) mod pe;
+pub(crate) mod plan;
+pub mod ratio;
+pub mod resolution;
+pub mod settings;
+pub mod store;
+
+pub use delta::{apply as apply_delta, bps::create as create_bps, ips::create as create_ips};
+pub use error_code::ErrorCode;
+pub use patch::{Binary, Manifest, Patch};
+pub use plan::{Change, Plan};
+pub use ratio::{Aspect, Ratio, RatioStr};
+pub use resolution::{Resolution, ResolutionStr};
+pub use settings::Settings;
+pub use store::Store;
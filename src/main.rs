@@ -1,150 +1,382 @@
 #![allow(clippy::try_err)]
 
 mod args;
-mod error_code;
-mod patch;
-mod ratio;
-mod resolution;
 
 use std::path::{Path, PathBuf};
 
 use clap::StructOpt;
-use registry::{Data, Hive, Security};
-
-use crate::args::Args;
-use crate::error_code::ErrorCode;
-use crate::patch::*;
-use crate::ratio::*;
-use crate::resolution::*;
-
-const ENTRY_KEY: &str = r"Software\Rage Games Ltd\eRacer";
-const EXECUTABLE: &str = r"eracer.exe";
-const INSTALLDIR_KEY: &str = r"HOVAPPDATA";
-const RESOLUTION_WIDTH_KEY: &str = r"PREFERRED WIDTH";
-const RESOLUTION_HEIGHT_KEY: &str = r"PREFERRED HEIGHT";
-
-struct Settings {
-    registry_path: PathBuf,
-    override_path: Option<PathBuf>,
-    resolution: Resolution,
-    binary: Option<Binary>,
+use eracer_config::{
+    apply_delta, create_bps, create_ips,
+    patch::*,
+    ratio::*,
+    settings::ENTRY_KEY,
+    store::{
+        self,
+        regfile::{self, RegFormat},
+        schema::Kind,
+    },
+    ErrorCode, Plan, Settings, Store,
+};
+
+use crate::args::{Args, Command};
+
+fn main() {
+    std::process::exit({
+        match app(Args::parse()) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("{}", e);
+                (&e).into()
+            }
+        }
+    });
 }
 
-impl std::fmt::Debug for Settings {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        fmt.debug_struct("Settings")
-            .field("registry_path", &self.registry_path)
-            .field("override_path", &self.override_path)
-            .field("path", &self.path())
-            .field("resolution", &self.resolution)
-            .field("binary", &self.binary)
-            .finish()
+fn app(args: Args) -> Result<(), ErrorCode> {
+    if let Some(Command::Discover) = args.command {
+        let prefixes = store::discover_all();
+        if prefixes.is_empty() {
+            println!("No Wine prefixes with e-Racer are found.");
+        }
+        for prefix in &prefixes {
+            println!("{}", prefix);
+        }
+        if !prefixes.is_empty() {
+            println!("Pass one of them via `--prefix <PATH>`.");
+        }
+
+        return Ok(());
     }
+
+    let store = store::locate(args.prefix.as_deref())?;
+    run(args, store, &mut Cache::open())
 }
 
-impl Settings {
-    pub fn load(override_path: Option<PathBuf>) -> Result<Self, ErrorCode> {
-        let entry = Hive::CurrentUser
-            .open(ENTRY_KEY, Security::Read)
-            .map_err(|_| ErrorCode::RegistryEntryNotFound)?;
-
-        let registry_path = match entry
-            .value(INSTALLDIR_KEY)
-            .map_err(|_| ErrorCode::RegistryInstallDirNotFound)?
-        {
-            Data::String(installed_dir) => {
-                Path::new(&installed_dir.to_os_string()).join(EXECUTABLE)
-            }
-            _ => Err(ErrorCode::RegistryInstallDirIncorrectType)?,
-        };
+/// `app` with the settings store and the cache given, so it can be run against a `MemoryStore`
+fn run(
+    mut args: Args,
+    mut store: Option<Box<dyn Store>>,
+    cache: &mut Cache,
+) -> Result<(), ErrorCode> {
+    let manifest = Manifest::load(args.manifest.as_deref())?;
+    let schema = manifest.schema();
 
-        let width = match entry
-            .value(RESOLUTION_WIDTH_KEY)
-            .map_err(|_| ErrorCode::RegistryResolutionWidthNotFound)?
-        {
-            Data::U32(width) => width,
-            _ => Err(ErrorCode::RegistryResolutionWidthIncorrectType)?,
+    // the key may not exist yet, so these do not need the settings
+    if let Some(Command::ExportReg {
+        ref output,
+        regedit4,
+    }) = args.command
+    {
+        let store = store.ok_or(ErrorCode::RegistryUnsupported)?;
+        let format = match regedit4 {
+            true => RegFormat::Regedit4,
+            false => RegFormat::Regedit5,
         };
+        let data = regfile::export(&store.values()?, format).map_err(ErrorCode::RegFile)?;
+        if args.dry_run {
+            let mut plan = Plan::default();
+            plan.file(output, Path::new(&format!(r"HKCU\{}", ENTRY_KEY)));
+            print_plan(&plan, args.json);
+            return Ok(());
+        }
+
+        std::fs::write(output, data).map_err(ErrorCode::IO)?;
+
+        println!("The settings have been exported to: {:?}", output);
+        return Ok(());
+    }
+
+    if let Some(Command::ImportReg { ref file }) = args.command {
+        let store = store.as_deref_mut().ok_or(ErrorCode::RegistryUnsupported)?;
+        let data = std::fs::read(file).map_err(ErrorCode::IO)?;
+        let values =
+            regfile::import(&data).map_err(|e| ErrorCode::RegFile(format!("{:?}: {}", file, e)))?;
+        if args.dry_run {
+            let mut plan = Plan::default();
+            for (name, value) in &values {
+                schema.check(name, value)?;
+                plan.value(
+                    ENTRY_KEY,
+                    name,
+                    store.read(name).ok().flatten().as_ref(),
+                    value,
+                );
+            }
+            print_plan(&plan, args.json);
+            return Ok(());
+        }
 
-        let height = match entry
-            .value(RESOLUTION_HEIGHT_KEY)
-            .map_err(|_| ErrorCode::RegistryResolutionHeightNotFound)?
-        {
-            Data::U32(height) => height,
-            _ => Err(ErrorCode::RegistryResolutionHeightIncorrectType)?,
+        regfile::apply(store, &schema, &values)?;
+
+        println!(
+            "{} values have been imported from: {:?}",
+            values.len(),
+            file
+        );
+        return Ok(());
+    }
+
+    if let Some(Command::Values) = args.command {
+        let store = store.ok_or(ErrorCode::RegistryUnsupported)?;
+        let values = store.values()?;
+        let row = |name: &str, kind: String, value: String, title: &str| {
+            let row = format!("{:<24} {:<14} {:<24} {}", name, kind, value, title);
+            println!("{}", row.trim_end());
         };
 
-        let binary = Binary::new(override_path.as_deref().unwrap_or_else(|| &registry_path)).ok();
+        for (name, value) in &values {
+            let title = schema.known(name).map_or("", |k| &k.title);
+            row(name, value.kind().to_owned(), value.to_string(), title);
+        }
+        for known in schema.values() {
+            if !values
+                .iter()
+                .any(|(n, _)| n.eq_ignore_ascii_case(&known.name))
+            {
+                let kind = known.kind.to_string();
+                row(&known.name, kind, "(not set)".to_owned(), &known.title);
+            }
+        }
+
+        return Ok(());
+    }
 
-        let this = Self {
-            registry_path,
-            override_path,
-            resolution: (width, height).into(),
-            binary,
+    if let Some(Command::GetValue { ref name }) = args.command {
+        let store = store.ok_or(ErrorCode::RegistryUnsupported)?;
+        let value = store
+            .read(name)?
+            .ok_or_else(|| ErrorCode::RegistryValueNotFound(name.clone()))?;
+        println!("{}", value);
+        return Ok(());
+    }
+
+    if let Some(Command::SetValue {
+        ref name,
+        ref value,
+        kind,
+    }) = args.command
+    {
+        let store = store.as_deref_mut().ok_or(ErrorCode::RegistryUnsupported)?;
+        let incorrect = |reason: String| ErrorCode::RegistryValueIncorrect(name.clone(), reason);
+
+        // the type of the existing value or, for a new one, of the known one
+        let current = store.read(name).ok().flatten();
+        let expected = match current {
+            Some(ref current) => Some(Kind::of(current).ok_or_else(|| {
+                incorrect(format!(
+                    "values of type {} cannot be changed",
+                    current.kind()
+                ))
+            })?),
+            None => schema.known(name).map(|k| k.kind),
+        };
+        let kind = match (kind, expected) {
+            (Some(kind), Some(expected)) if kind != expected => {
+                Err(incorrect(format!("expected {}, given {}", expected, kind)))?
+            }
+            (Some(kind), _) | (None, Some(kind)) => kind,
+            (None, None) => Err(incorrect(
+                "the type of a new value must be given via `--type`".to_owned(),
+            ))?,
         };
 
-        Ok(this)
+        let value = kind.parse(value).map_err(incorrect)?;
+        schema.check(name, &value)?;
+        if args.dry_run {
+            let mut plan = Plan::default();
+            plan.value(ENTRY_KEY, name, current.as_ref(), &value);
+            print_plan(&plan, args.json);
+            return Ok(());
+        }
+
+        store.write(name, &value)?;
+
+        println!("`{}` has been set to: {}", name, value);
+        return Ok(());
     }
 
-    pub fn path(&self) -> &Path {
-        self.override_path
-            .as_deref()
-            .unwrap_or_else(|| self.registry_path.as_path())
+    let mut settings = Settings::load(store, args.binary_path.clone(), &manifest, cache)?;
+
+    if settings.binary().is_none() {
+        let signature = args.signature.clone().unwrap_or_default();
+        match Binary::from_signature(settings.path(), &signature) {
+            Ok(binary) if args.allow_unknown => settings.set_binary(Some(binary)),
+            Ok(binary) => eprintln!(
+                "Unknown version of the binary ({:?}), but the aspect ratio is found by signature: {:?}. Passthrough key `--allow-unknown` to patch it anyway.",
+                settings.path(),
+                binary
+            ),
+            Err(e) if args.allow_unknown => Err(e)?,
+            Err(ErrorCode::SignatureAmbiguous(_, offsets)) => eprintln!(
+                "Unknown version of the binary ({:?}), the aspect ratio candidates are found at: {:#010X?}",
+                settings.path(),
+                offsets
+            ),
+            Err(_) => {}
+        }
     }
 
-    pub fn set_resolution(&mut self, resolution: impl Into<Resolution>) -> Result<(), ErrorCode> {
-        let resolution: Resolution = resolution.into();
+    if let Some(Command::Restore) = args.command {
+        let backup = Backup::open(&Backup::path_for(
+            settings.path(),
+            args.backup_dir.as_deref(),
+        ))?;
 
-        let entry = Hive::CurrentUser
-            .open(ENTRY_KEY, Security::Write)
-            .map_err(|_| ErrorCode::RegistryEntryNotFound)?;
+        if args.dry_run {
+            backup.check(settings.path(), &manifest)?;
+            let mut plan = Plan::default();
+            plan.file(settings.path(), backup.path());
+            print_plan(&plan, args.json);
+            return Ok(());
+        }
 
-        entry
-            .set_value(RESOLUTION_WIDTH_KEY, &Data::U32(resolution.width))
-            .map_err(|_| ErrorCode::RegistryResolutionWidthChange)?;
+        backup.restore(settings.path(), &manifest)?;
+        println!("The binary has been restored from: {:?}", backup.path());
 
-        self.resolution.width = resolution.width;
+        // a backup of an unknown binary is restored as it is
+        match Binary::new(settings.path(), &manifest) {
+            Ok(mut binary) => {
+                if binary.aspect() != Aspect::from(*RATIO_ORIGINAL) {
+                    binary.set_ratio(Ratio::ORIGINAL)?;
+                }
+                binary.verify_original()?;
+                cache.store(&binary);
+            }
+            Err(ErrorCode::IncorrectHashOfBinary(_)) => {
+                println!("The restored binary is not a known version.")
+            }
+            Err(e) => Err(e)?,
+        }
 
-        entry
-            .set_value(RESOLUTION_HEIGHT_KEY, &Data::U32(resolution.height))
-            .map_err(|_| ErrorCode::RegistryResolutionHeightChange)?;
+        return Ok(());
+    }
 
-        self.resolution.height = resolution.height;
+    let references = |reference: &[PathBuf]| {
+        let mut references = reference.to_vec();
+        let backup = Backup::path_for(settings.path(), args.backup_dir.as_deref());
+        if backup.exists() {
+            references.push(backup);
+        }
+        references
+    };
 
-        Ok(())
+    if let Some(Command::Diagnose { ref reference }) = args.command {
+        let references = references(reference);
+        print!("{}", Report::new(settings.path(), &manifest, &references)?);
+        return Ok(());
     }
-}
 
-fn main() {
-    std::process::exit({
-        match app(Args::parse()) {
-            Ok(()) => 0,
-            Err(e) => {
-                eprintln!("{}", e);
-                (&e).into()
+    if let Some(Command::Verify { ref reference }) = args.command {
+        let references = references(reference);
+        let report = BlockReport::new(settings.path(), &manifest, &references)?;
+        print!("{}", report);
+        return match report.is_intact() {
+            true => Ok(()),
+            false => Err(report.error()),
+        };
+    }
+
+    if let Some(Command::HashBlocks { block_size }) = args.command {
+        let binary = settings.binary().ok_or_else(|| {
+            ErrorCode::IncorrectHashOfBinary(settings.path().to_string_lossy().to_string())
+        })?;
+        let blocks = binary.blocks(block_size.unwrap_or(DEFAULT_BLOCK_SIZE))?;
+        let size = std::fs::metadata(binary.path())
+            .map_err(ErrorCode::IO)?
+            .len();
+        print!("size = {}\n{}", size, blocks.to_toml());
+        return Ok(());
+    }
+
+    if args.reset_aspect_ratio {
+        args.set_aspect_ratio = Some(Ratio::ORIGINAL);
+    }
+
+    if args.dry_run {
+        let mut plan = settings.plan(args.set_resolution, args.set_aspect_ratio);
+        match args.command {
+            Some(Command::ExportPatch { ratio, ref output }) => {
+                let binary = settings.binary().ok_or_else(|| {
+                    ErrorCode::IncorrectHashOfBinary(settings.path().to_string_lossy().to_string())
+                })?;
+                binary.ratio_patch(ratio)?;
+                for extension in ["ips", "bps"] {
+                    let path = patch_output(ratio, output.as_deref()).with_extension(extension);
+                    plan.file(&path, binary.path());
+                }
+            }
+            Some(Command::ApplyPatch { ref file }) => {
+                let path = settings.path();
+                let patch = std::fs::read(file).map_err(ErrorCode::IO)?;
+                let source = std::fs::read(path).map_err(ErrorCode::IO)?;
+                let target = apply_delta(&source, &patch)
+                    .map_err(|e| ErrorCode::PatchFile(format!("{:?}: {}", file, e)))?;
+                plan.diff(path, &source, &target);
             }
+            _ => {}
         }
-    });
-}
+        print_plan(&plan, args.json);
 
-fn app(mut args: Args) -> Result<(), ErrorCode> {
-    let mut settings = Settings::load(args.binary_path.clone())?;
+        return Ok(());
+    }
+
+    if let Some(Command::ExportPatch { ratio, ref output }) = args.command {
+        let binary = settings.binary().ok_or_else(|| {
+            ErrorCode::IncorrectHashOfBinary(settings.path().to_string_lossy().to_string())
+        })?;
+        let (source, target) = binary.ratio_patch(ratio)?;
+
+        let output = patch_output(ratio, output.as_deref());
+        let ips = create_ips(&source, &target).map_err(ErrorCode::PatchFile)?;
+        let bps = create_bps(&source, &target).map_err(ErrorCode::PatchFile)?;
+
+        for (extension, data) in [("ips", ips), ("bps", bps)] {
+            let path = output.with_extension(extension);
+            std::fs::write(&path, data).map_err(ErrorCode::IO)?;
+            println!("A patch has been written: {:?}", path);
+        }
+
+        return Ok(());
+    }
+
+    if let Some(Command::ApplyPatch { ref file }) = args.command {
+        let path = settings.path().to_owned();
+        let patch = std::fs::read(file).map_err(ErrorCode::IO)?;
+        let source = std::fs::read(&path).map_err(ErrorCode::IO)?;
+        let target = apply_delta(&source, &patch)
+            .map_err(|e| ErrorCode::PatchFile(format!("{:?}: {}", file, e)))?;
+
+        Backup::create(&path, args.backup_dir.as_deref(), &manifest)?;
+        let sha2 = replace_file(&path, &target)?;
+        settings.set_binary(Binary::new_cached(&path, &manifest, cache).ok());
+
+        println!("A patch has been applied: {:?}", file);
+        println!("sha256 of the patched binary: {}", sha256(&sha2));
+        match settings.binary() {
+            Some(binary) => println!("The binary is recognized: {:#?}", binary),
+            None => println!("The binary is not recognized as a known version anymore, the aspect ratio cannot be changed"),
+        }
+
+        return Ok(());
+    }
 
     if let Some(resolution) = args.set_resolution {
         settings.set_resolution(resolution)?;
         println!("A resolution has been set to: {}", resolution);
     }
 
-    if args.reset_aspect_ratio {
-        args.set_aspect_ratio = Some(Ratio::Original);
-    }
-
     if let Some(ratio) = args.set_aspect_ratio {
-        if let Some(ref mut binary) = settings.binary {
-            binary.set_ratio(ratio)?;
+        if let Some(binary) = settings.binary_mut() {
+            Backup::create(binary.path(), args.backup_dir.as_deref(), &manifest)?;
+            let sha2 = binary.set_ratio(ratio)?;
+            cache.store(binary);
             println!("A ratio has been set to: {}", ratio);
+            println!("sha256 of the patched binary: {}", sha256(&sha2));
         } else {
-            println!("File not found or unknown version of the binary ({:?})", settings.path());
+            println!(
+                "File not found or unknown version of the binary ({:?})",
+                settings.path()
+            );
         }
     }
 
@@ -155,3 +387,362 @@ fn app(mut args: Args) -> Result<(), ErrorCode> {
 
     Ok(())
 }
+
+/// Path of the exported patch files without extension
+fn patch_output(ratio: Ratio, output: Option<&Path>) -> PathBuf {
+    output.map_or_else(
+        || format!("eracer_{}-{}", ratio.w(), ratio.h()).into(),
+        Path::to_owned,
+    )
+}
+
+fn print_plan(plan: &Plan, json: bool) {
+    match json {
+        true => println!("{}", plan.to_json()),
+        false => print!("{}", plan.to_table()),
+    }
+}
+
+/// sha256 as printed by `sha256sum`
+fn sha256(sha2: &[u8]) -> String {
+    sha2.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use eracer_config::{
+        settings::{INSTALLDIR_KEY, RESOLUTION_HEIGHT_KEY, RESOLUTION_WIDTH_KEY},
+        store::{MemoryStore, Value},
+    };
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    const RATIO_OFFSET: usize = 0x100;
+
+    /// A game dir with `eracer.exe` and a manifest that describes it
+    fn game(dir: &Path) -> (MemoryStore, PathBuf) {
+        let mut data = vec![0; 0x1000];
+        data[RATIO_OFFSET..RATIO_OFFSET + 3].copy_from_slice(RATIO_ORIGINAL);
+        data[RATIO_OFFSET + 3] = RATIO_HIGH_BYTE;
+        std::fs::write(dir.join("eracer.exe"), &data).unwrap();
+
+        let manifest = dir.join("manifest.toml");
+        let toml = format!(
+            "[[version]]\nname = \"Test\"\nsha256 = \"{}\"\n\n[[version.site]]\nname = \"ratio\"\noffset = {}\noriginal = \"3A 46 71\"\n",
+            sha256(&Sha256::digest(&data)),
+            RATIO_OFFSET
+        );
+        std::fs::write(&manifest, toml).unwrap();
+
+        let store = MemoryStore::new([
+            (
+                INSTALLDIR_KEY,
+                Value::String(dir.to_string_lossy().to_string()),
+            ),
+            (RESOLUTION_WIDTH_KEY, Value::U32(640)),
+            (RESOLUTION_HEIGHT_KEY, Value::U32(480)),
+        ]);
+
+        (store, manifest)
+    }
+
+    fn run_with(store: Option<&MemoryStore>, args: &[&str]) -> Result<(), ErrorCode> {
+        let args = Args::parse_from(std::iter::once("eracer-config").chain(args.iter().copied()));
+        let store = store.map(|s| Box::new(s.clone()) as Box<dyn Store>);
+        run(args, store, &mut Cache::in_memory())
+    }
+
+    #[test]
+    fn app_set_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, manifest) = game(dir.path());
+        let manifest = manifest.to_str().unwrap();
+        let exe = dir.path().join("eracer.exe");
+        let original = std::fs::read(&exe).unwrap();
+
+        let args = [
+            "--manifest",
+            manifest,
+            "--set-resolution",
+            "1920x1080",
+            "--set-aspect-ratio",
+            "16:9",
+        ];
+        run_with(Some(&store), &args).unwrap();
+        assert_eq!(
+            store.read(RESOLUTION_WIDTH_KEY).unwrap(),
+            Some(Value::U32(1920))
+        );
+        assert_eq!(
+            store.read(RESOLUTION_HEIGHT_KEY).unwrap(),
+            Some(Value::U32(1080))
+        );
+
+        let data = std::fs::read(&exe).unwrap();
+        let ratio = Ratio::new(16, 9).unwrap();
+        assert_eq!(data[RATIO_OFFSET..RATIO_OFFSET + 3], ratio.hex());
+        assert!(Backup::path_for(&exe, None).exists());
+
+        run_with(
+            Some(&store),
+            &["--manifest", manifest, "--dry-run", "restore"],
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), data);
+
+        run_with(Some(&store), &["--manifest", manifest, "restore"]).unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), original);
+    }
+
+    #[test]
+    fn app_patch_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, manifest) = game(dir.path());
+        let manifest = manifest.to_str().unwrap();
+        let exe = dir.path().join("eracer.exe");
+        let original = std::fs::read(&exe).unwrap();
+        let output = dir.path().join("wide");
+        let output = output.to_str().unwrap();
+
+        let export = ["export-patch", "16:9", "--output", output];
+        run_with(
+            Some(&store),
+            &[&["--manifest", manifest, "--dry-run"], &export[..]].concat(),
+        )
+        .unwrap();
+        assert!(!dir.path().join("wide.ips").exists());
+        run_with(
+            Some(&store),
+            &[&["--manifest", manifest], &export[..]].concat(),
+        )
+        .unwrap();
+        assert!(dir.path().join("wide.ips").exists());
+
+        let ips = dir.path().join("wide.ips");
+        let args = ["--manifest", manifest, "--dry-run", "apply-patch"];
+        run_with(
+            Some(&store),
+            &[&args[..], &[ips.to_str().unwrap()]].concat(),
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), original);
+
+        // a corrupt patch is caught without touching the binary
+        let corrupt = dir.path().join("corrupt.ips");
+        let mut data = std::fs::read(&ips).unwrap();
+        data.truncate(data.len() - 4);
+        std::fs::write(&corrupt, data).unwrap();
+        assert!(matches!(
+            run_with(
+                Some(&store),
+                &[&args[..], &[corrupt.to_str().unwrap()]].concat()
+            ),
+            Err(ErrorCode::PatchFile(_))
+        ));
+        assert_eq!(std::fs::read(&exe).unwrap(), original);
+    }
+
+    #[test]
+    fn app_without_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, manifest) = game(dir.path());
+        let manifest = manifest.to_str().unwrap();
+        let exe = dir.path().join("eracer.exe");
+
+        assert!(matches!(
+            run_with(None, &["--manifest", manifest]),
+            Err(ErrorCode::RegistryUnsupported)
+        ));
+
+        let args = [
+            "--manifest",
+            manifest,
+            "--binary-path",
+            exe.to_str().unwrap(),
+        ];
+        run_with(None, &[&args[..], &["--set-aspect-ratio", "21:9"]].concat()).unwrap();
+        assert!(matches!(
+            run_with(
+                None,
+                &[&args[..], &["--set-resolution", "800x600"]].concat()
+            ),
+            Err(ErrorCode::RegistryUnsupported)
+        ));
+
+        // a backup of an unknown binary is restored as it is, and that is not an error
+        let other = dir.path().join("other.exe");
+        std::fs::write(&other, b"unknown").unwrap();
+        Backup::create(&other, None, &Manifest::embedded()).unwrap();
+        std::fs::write(&other, b"changed").unwrap();
+        let args = [
+            "--manifest",
+            manifest,
+            "--binary-path",
+            other.to_str().unwrap(),
+        ];
+        run_with(None, &[&args[..], &["restore"]].concat()).unwrap();
+        assert_eq!(std::fs::read(&other).unwrap(), b"unknown");
+    }
+
+    #[test]
+    fn app_wine_prefix() {
+        let prefix = tempfile::tempdir().unwrap();
+        let dir = prefix.path().join("drive_c").join("Games").join("eRacer");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (_, manifest) = game(&dir);
+
+        let user_reg = prefix.path().join("user.reg");
+        let content = r#"WINE REGISTRY Version 2
+
+[Software\\Rage Games Ltd\\eRacer] 1700000000
+"HOVAPPDATA"="C:\\Games\\eRacer"
+"PREFERRED HEIGHT"=dword:000001e0
+"PREFERRED WIDTH"=dword:00000280
+"#;
+        std::fs::write(&user_reg, content).unwrap();
+
+        let args = Args::parse_from([
+            "eracer-config",
+            "--manifest",
+            manifest.to_str().unwrap(),
+            "--set-resolution",
+            "1024x768",
+            "--set-aspect-ratio",
+            "16:10",
+        ]);
+        let store = store::locate(Some(prefix.path())).unwrap();
+        run(args, store, &mut Cache::in_memory()).unwrap();
+
+        let expected = content
+            .replace("dword:000001e0", "dword:00000300")
+            .replace("dword:00000280", "dword:00000400");
+        assert_eq!(std::fs::read_to_string(&user_reg).unwrap(), expected);
+
+        let data = std::fs::read(dir.join("eracer.exe")).unwrap();
+        let ratio = Ratio::new(16, 10).unwrap();
+        assert_eq!(data[RATIO_OFFSET..RATIO_OFFSET + 3], ratio.hex());
+    }
+
+    #[test]
+    fn app_export_import_reg() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, _) = game(dir.path());
+        let file = dir.path().join("eracer.reg");
+        let file = file.to_str().unwrap();
+
+        run_with(Some(&store), &["--dry-run", "export-reg", file]).unwrap();
+        assert!(!Path::new(file).exists());
+        run_with(Some(&store), &["export-reg", file]).unwrap();
+        let copy = MemoryStore::default();
+        run_with(Some(&copy), &["--dry-run", "import-reg", file]).unwrap();
+        assert!(copy.values().unwrap().is_empty());
+        run_with(Some(&copy), &["import-reg", file]).unwrap();
+        assert_eq!(copy.values().unwrap(), store.values().unwrap());
+
+        std::fs::write(
+            file,
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Rage Games Ltd\\eRacer]\n\"PREFERRED WIDTH\"=dword:00000100\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            run_with(Some(&copy), &["import-reg", file]),
+            Err(ErrorCode::RegistryValueIncorrect(_, _))
+        ));
+        assert_eq!(
+            copy.read(RESOLUTION_WIDTH_KEY).unwrap(),
+            Some(Value::U32(640))
+        );
+        assert!(matches!(
+            run_with(None, &["import-reg", file]),
+            Err(ErrorCode::RegistryUnsupported)
+        ));
+    }
+
+    #[test]
+    fn app_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, _) = game(dir.path());
+        let incorrect = |args: &[&str]| {
+            matches!(
+                run_with(Some(&store), args),
+                Err(ErrorCode::RegistryValueIncorrect(_, _))
+            )
+        };
+
+        run_with(Some(&store), &["values"]).unwrap();
+        run_with(Some(&store), &["get-value", "preferred width"]).unwrap();
+        assert!(matches!(
+            run_with(Some(&store), &["get-value", "Sound"]),
+            Err(ErrorCode::RegistryValueNotFound(_))
+        ));
+
+        run_with(
+            Some(&store),
+            &["--dry-run", "set-value", "PREFERRED WIDTH", "800"],
+        )
+        .unwrap();
+        assert_eq!(
+            store.read(RESOLUTION_WIDTH_KEY).unwrap(),
+            Some(Value::U32(640))
+        );
+        run_with(Some(&store), &["set-value", "PREFERRED WIDTH", "0x400"]).unwrap();
+        assert_eq!(
+            store.read(RESOLUTION_WIDTH_KEY).unwrap(),
+            Some(Value::U32(1024))
+        );
+        assert!(incorrect(&["set-value", "PREFERRED WIDTH", "320"]));
+        assert!(incorrect(&["set-value", "PREFERRED WIDTH", "wide"]));
+        assert!(incorrect(&[
+            "set-value",
+            "PREFERRED WIDTH",
+            "800",
+            "--type",
+            "REG_SZ"
+        ]));
+
+        // new values need a type
+        assert!(incorrect(&["set-value", "Sound", "01 02"]));
+        run_with(
+            Some(&store),
+            &["set-value", "Sound", "01 02", "--type", "binary"],
+        )
+        .unwrap();
+        assert_eq!(
+            store.read("sound").unwrap(),
+            Some(Value::Binary(vec![1, 2]))
+        );
+        assert!(incorrect(&["set-value", "Sound", "3"]));
+
+        store
+            .clone()
+            .write("Path", &Value::Other(2, vec![b'a', 0, 0, 0]))
+            .unwrap();
+        assert!(incorrect(&["set-value", "Path", "b"]));
+
+        // values described by a manifest are checked as the built-in ones
+        let manifest = dir.path().join("values.toml");
+        std::fs::write(
+            &manifest,
+            "[[value]]\nname = \"Detail\"\ntitle = \"Level of detail\"\ntype = \"REG_DWORD\"\nmax = 2\n",
+        )
+        .unwrap();
+        let manifest = manifest.to_str().unwrap();
+        run_with(
+            Some(&store),
+            &["--manifest", manifest, "set-value", "Detail", "2"],
+        )
+        .unwrap();
+        assert_eq!(store.read("detail").unwrap(), Some(Value::U32(2)));
+        assert!(incorrect(&[
+            "--manifest",
+            manifest,
+            "set-value",
+            "Detail",
+            "3"
+        ]));
+        assert!(incorrect(&["set-value", "Other", "3"]));
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{replace_file, Binary, Manifest};
+use crate::{error_code::ErrorCode, hex};
+
+const BACKUP_EXTENSION: &str = "bak";
+const RECORD_EXTENSION: &str = "sha256";
+
+/// A copy of the binary made before its first modification. The hash of the copy is recorded in
+/// a sidecar file (`eracer.exe.bak.sha256`, in `sha256sum` format) to detect a damaged backup.
+#[derive(Debug)]
+pub struct Backup {
+    path: PathBuf,
+    sha2: [u8; 32],
+}
+
+impl Backup {
+    /// Path of the backup of `binary`: next to it or inside of `dir`
+    pub fn path_for(binary: &Path, dir: Option<&Path>) -> PathBuf {
+        let mut name = binary.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(BACKUP_EXTENSION);
+
+        match dir {
+            Some(dir) => dir.join(name),
+            None => binary.with_file_name(name),
+        }
+    }
+
+    /// Makes a backup of `binary` unless it already exists. An existing backup is reused only if
+    /// it is a backup of the same version (see `check`).
+    pub fn create(
+        binary: &Path,
+        dir: Option<&Path>,
+        manifest: &Manifest,
+    ) -> Result<Self, ErrorCode> {
+        let path = Self::path_for(binary, dir);
+        if path.exists() {
+            let backup = Self::open(&path)?;
+            backup.check(binary, manifest)?;
+            return Ok(backup);
+        }
+
+        // the record is written first: a copy without it would block every later backup
+        let sha2 = sha256(binary)?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let record = format!("{}  {}\n", hex::encode(&sha2), name);
+        std::fs::write(record_path(&path), record).map_err(ErrorCode::IO)?;
+
+        let copied = std::fs::copy(binary, &path)
+            .map_err(ErrorCode::IO)
+            .and_then(|_| sha256(&path))
+            .and_then(|copied| match copied == sha2 {
+                true => Ok(()),
+                false => Err(ErrorCode::BackupCorrupted(
+                    path.to_string_lossy().to_string(),
+                )),
+            });
+        if let Err(e) = copied {
+            let _ = std::fs::remove_file(&path);
+            let _ = std::fs::remove_file(record_path(&path));
+            Err(e)?;
+        }
+
+        Ok(Self { path, sha2 })
+    }
+
+    /// Opens an existing backup and checks it against the recorded hash
+    pub fn open(path: &Path) -> Result<Self, ErrorCode> {
+        let path_str = path.to_string_lossy().to_string();
+        if !path.exists() {
+            Err(ErrorCode::BackupNotFound(path_str.clone()))?;
+        }
+
+        let record = std::fs::read_to_string(record_path(path)).map_err(ErrorCode::IO)?;
+        let recorded = record
+            .split_whitespace()
+            .next()
+            .and_then(|s| hex::parse(s).ok())
+            .and_then(|v| <[u8; 32]>::try_from(v).ok())
+            .ok_or_else(|| ErrorCode::BackupCorrupted(path_str.clone()))?;
+
+        let sha2 = sha256(path)?;
+        if sha2 != recorded {
+            Err(ErrorCode::BackupCorrupted(path_str))?;
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            sha2,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Checks that the backup is a copy of the version `binary` was made from: a known version
+    /// must be recognized in the backup as well, and if the backup is of a known version, `binary`
+    /// must be that version or not recognized anymore (e.g. after `apply-patch`). A backup of an
+    /// unknown binary is accepted for an unknown binary of the same size only.
+    pub fn check(&self, binary: &Path, manifest: &Manifest) -> Result<(), ErrorCode> {
+        let version = |path| Binary::new(path, manifest).map(|b| b.patch().name().to_owned());
+        let mismatch = |reason: String| {
+            ErrorCode::BackupMismatch(self.path.to_string_lossy().to_string(), reason)
+        };
+
+        match (version(&self.path).ok(), version(binary).ok()) {
+            (Some(backup), Some(current)) if backup != current => Err(mismatch(format!(
+                "it is `{}`, but the binary is `{}`",
+                backup, current
+            ))),
+            (None, Some(current)) => Err(mismatch(format!(
+                "it is not a known version, but the binary is `{}`",
+                current
+            ))),
+            (None, None) if file_size(&self.path)? != file_size(binary)? => Err(mismatch(
+                "neither it nor the binary is a known version, and their sizes differ".to_owned(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces `binary` by the backup (through a temporary file, see `replace_file`) after
+    /// checking that the backup belongs to it
+    pub fn restore(&self, binary: &Path, manifest: &Manifest) -> Result<(), ErrorCode> {
+        self.check(binary, manifest)?;
+
+        let data = std::fs::read(&self.path).map_err(ErrorCode::IO)?;
+        let sha2: [u8; 32] = Sha256::digest(&data).into();
+        if sha2 != self.sha2 {
+            Err(ErrorCode::BackupCorrupted(
+                self.path.to_string_lossy().to_string(),
+            ))?;
+        }
+
+        if replace_file(binary, &data)? != self.sha2 {
+            Err(ErrorCode::RestoredHashMismatch(
+                binary.to_string_lossy().to_string(),
+            ))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn file_size(path: &Path) -> Result<u64, ErrorCode> {
+    Ok(std::fs::metadata(path).map_err(ErrorCode::IO)?.len())
+}
+
+fn record_path(backup: &Path) -> PathBuf {
+    let mut name = backup.as_os_str().to_os_string();
+    name.push(".");
+    name.push(RECORD_EXTENSION);
+    name.into()
+}
+
+/// sha256 of the whole file
+pub fn sha256(path: &Path) -> Result<[u8; 32], ErrorCode> {
+    let file = File::open(path).map_err(ErrorCode::IO)?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 0x10000];
+
+    loop {
+        let count = reader.read(&mut buffer).map_err(ErrorCode::IO)?;
+        if count == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..count]);
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::patch::test::{binary, manifest, write_binary, RATIO_OFFSET};
+
+    #[test]
+    fn backup_create_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("eracer.exe");
+        std::fs::write(&binary, b"original").unwrap();
+        let manifest = Manifest::embedded();
+
+        let backup = Backup::create(&binary, None, &manifest).unwrap();
+        assert_eq!(backup.path(), dir.path().join("eracer.exe.bak"));
+        assert!(dir.path().join("eracer.exe.bak.sha256").exists());
+
+        // the existing backup is kept as is
+        std::fs::write(&binary, b"modified").unwrap();
+        Backup::create(&binary, None, &manifest).unwrap();
+        assert_eq!(std::fs::read(backup.path()).unwrap(), b"original");
+
+        backup.restore(&binary, &manifest).unwrap();
+        assert_eq!(std::fs::read(&binary).unwrap(), b"original");
+    }
+
+    #[test]
+    fn backup_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let backups = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("eracer.exe");
+        std::fs::write(&binary, b"original").unwrap();
+        let manifest = Manifest::embedded();
+
+        // nothing is left behind by a failed backup, so the next one is made
+        let missing = backups.path().join("missing");
+        assert!(Backup::create(&binary, Some(&missing), &manifest).is_err());
+        std::fs::create_dir(&missing).unwrap();
+        Backup::create(&binary, Some(&missing), &manifest).unwrap();
+
+        let backup = Backup::create(&binary, Some(backups.path()), &manifest).unwrap();
+        assert_eq!(backup.path(), backups.path().join("eracer.exe.bak"));
+        assert!(Backup::open(&Backup::path_for(&binary, None)).is_err());
+    }
+
+    #[test]
+    fn backup_corrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("eracer.exe");
+        std::fs::write(&binary, b"original").unwrap();
+        let manifest = Manifest::embedded();
+
+        let backup = Backup::create(&binary, None, &manifest).unwrap();
+        std::fs::write(backup.path(), b"damaged").unwrap();
+
+        assert!(matches!(
+            Backup::open(backup.path()),
+            Err(ErrorCode::BackupCorrupted(_))
+        ));
+    }
+
+    #[test]
+    fn backup_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = manifest();
+        let path = write_binary(dir.path(), &binary());
+
+        // a backup of the known version is reused after the ratio is patched
+        let backup = Backup::create(&path, None, &manifest).unwrap();
+        let mut patched = binary();
+        patched[RATIO_OFFSET] = 0;
+        std::fs::write(&path, &patched).unwrap();
+        Backup::create(&path, None, &manifest).unwrap();
+        backup.restore(&path, &manifest).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), binary());
+
+        // a stale backup of something else is neither reused nor restored
+        std::fs::write(backup.path(), b"stale").unwrap();
+        std::fs::write(
+            record_path(backup.path()),
+            format!(
+                "{}  eracer.exe.bak\n",
+                hex::encode(&Sha256::digest(b"stale"))
+            ),
+        )
+        .unwrap();
+        assert!(matches!(
+            Backup::create(&path, None, &manifest),
+            Err(ErrorCode::BackupMismatch(_, _))
+        ));
+        let backup = Backup::open(backup.path()).unwrap();
+        assert!(matches!(
+            backup.restore(&path, &manifest),
+            Err(ErrorCode::BackupMismatch(_, _))
+        ));
+        assert_eq!(std::fs::read(&path).unwrap(), binary());
+    }
+}
@@ -0,0 +1,295 @@
+use std::{fmt::Display, path::Path, path::PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::{diagnose::SiteState, Binary, Manifest, Site};
+use crate::{error_code::ErrorCode, hex, pe::PeImage};
+
+pub const DEFAULT_BLOCK_SIZE: usize = 0x1000;
+/// Hashes of blocks are sha256 truncated to this length
+pub const BLOCK_HASH_LENGTH: usize = 8;
+
+/// Hashes of fixed-size blocks of the original release with patch sites containing their
+/// original bytes, so a modified block can be pinpointed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blocks {
+    pub size: usize,
+    pub hashes: Vec<[u8; BLOCK_HASH_LENGTH]>,
+}
+
+impl Blocks {
+    pub fn compute(data: &[u8], sites: &[Site], size: usize) -> Self {
+        let mut data = data.to_vec();
+        for site in sites {
+            if let Some(range) = data.get_mut(site.range()) {
+                range.copy_from_slice(&site.original);
+            }
+        }
+
+        let hashes = data
+            .chunks(size)
+            .map(|block| {
+                let mut hash = [0; BLOCK_HASH_LENGTH];
+                hash.copy_from_slice(&Sha256::digest(block)[..BLOCK_HASH_LENGTH]);
+                hash
+            })
+            .collect();
+
+        Self { size, hashes }
+    }
+
+    /// Indices of blocks that differ from `other`, including blocks missing in either of them
+    pub fn differing(&self, other: &Blocks) -> Vec<usize> {
+        let count = self.hashes.len().max(other.hashes.len());
+        (0..count)
+            .filter(|&i| self.hashes.get(i) != other.hashes.get(i))
+            .collect()
+    }
+
+    /// `blocks = [...]` entry of the manifest
+    pub fn to_toml(&self) -> String {
+        let mut toml = format!("block_size = {:#X}\nblocks = [\n", self.size);
+        for hash in &self.hashes {
+            toml.push_str(&format!("    \"{}\",\n", hex::encode(hash)));
+        }
+        toml.push_str("]\n");
+        toml
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockState {
+    Modified,
+    /// filled with zeros, as it happens to damaged sectors
+    Zeroed,
+    /// beyond the end of the file (truncated) or of the release (appended)
+    Missing,
+}
+
+/// Result of comparing `eracer.exe` with block hashes of a known version
+#[derive(Debug)]
+pub struct BlockReport {
+    path: PathBuf,
+    version: String,
+    /// the local copy the hashes are computed from if the manifest does not list them
+    reference: Option<PathBuf>,
+    block_size: usize,
+    total: usize,
+    sites: Vec<(String, SiteState)>,
+    blocks: Vec<(usize, BlockState)>,
+}
+
+impl BlockReport {
+    /// Compares the file with every version that has block hashes and keeps the closest one.
+    /// `references` are local copies of known versions (e.g. the backup): hashes of a version the
+    /// manifest has no blocks for are computed from its copy.
+    pub fn new(
+        path: &Path,
+        manifest: &Manifest,
+        references: &[PathBuf],
+    ) -> Result<Self, ErrorCode> {
+        let path_str = path.to_string_lossy().to_string();
+        let data = std::fs::read(path).map_err(|_| ErrorCode::NotFoundBinary(path_str.clone()))?;
+        let pe = PeImage::parse(&data).ok();
+
+        let local = references
+            .iter()
+            .filter_map(|path| {
+                let binary = Binary::new(path, manifest).ok()?;
+                let blocks = binary.blocks(DEFAULT_BLOCK_SIZE).ok()?;
+                Some((path, *binary.patch.sha2(), blocks))
+            })
+            .collect::<Vec<_>>();
+
+        manifest
+            .patches(pe.as_ref())
+            .iter()
+            .filter_map(|patch| {
+                let (expected, reference) = match patch.blocks() {
+                    Some(blocks) => (blocks, None),
+                    None => local
+                        .iter()
+                        .find(|(_, sha2, _)| sha2 == patch.sha2())
+                        .map(|(path, _, blocks)| (blocks, Some(path.to_path_buf())))?,
+                };
+                let actual = Blocks::compute(&data, patch.sites(), expected.size);
+
+                let blocks = actual
+                    .differing(expected)
+                    .into_iter()
+                    .map(|index| {
+                        let block = data.chunks(expected.size).nth(index);
+                        let state = match block {
+                            _ if index >= expected.hashes.len() => BlockState::Missing,
+                            None => BlockState::Missing,
+                            Some(block) if block.iter().all(|&b| b == 0) => BlockState::Zeroed,
+                            Some(_) => BlockState::Modified,
+                        };
+                        (index, state)
+                    })
+                    .collect();
+
+                let sites = patch
+                    .sites()
+                    .iter()
+                    .map(|site| (site.name.clone(), SiteState::of(site, &data)))
+                    .collect();
+
+                Some(Self {
+                    path: path.to_owned(),
+                    version: patch.name().to_owned(),
+                    reference,
+                    block_size: expected.size,
+                    total: expected.hashes.len(),
+                    sites,
+                    blocks,
+                })
+            })
+            .min_by_key(|report: &Self| report.blocks.len())
+            .ok_or(ErrorCode::BlocksUnknown(path_str))
+    }
+
+    pub fn is_intact(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The error to exit with if the file is not intact
+    pub fn error(&self) -> ErrorCode {
+        ErrorCode::BlocksMismatch(self.path.to_string_lossy().to_string(), self.blocks.len())
+    }
+}
+
+impl Display for BlockReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "File: {:?}", self.path)?;
+        writeln!(
+            f,
+            "Version: {} ({} blocks of {} bytes)",
+            self.version, self.total, self.block_size
+        )?;
+        if let Some(ref reference) = self.reference {
+            writeln!(f, "Hashes of blocks: computed from {:?}", reference)?;
+        }
+
+        writeln!(f, "\r\nPatch sites (changes of them are expected):")?;
+        for (name, state) in &self.sites {
+            writeln!(f, "  * {}: {}", name, state)?;
+        }
+
+        match self.blocks.len() {
+            0 => writeln!(f, "\r\nAll blocks match the original release.")?,
+            count => writeln!(
+                f,
+                "\r\nBlocks that differ from the original release: {}",
+                count
+            )?,
+        }
+
+        for &(index, state) in &self.blocks {
+            let start = index * self.block_size;
+            let state = match state {
+                BlockState::Modified => "modified (another mod?)",
+                BlockState::Zeroed => "zeroed (disk corruption?)",
+                BlockState::Missing => "missing or extra (truncated or appended file?)",
+            };
+            writeln!(
+                f,
+                "  * {:#010X}..{:#010X}: {}",
+                start,
+                start + self.block_size,
+                state
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::patch::{test::*, Binary};
+
+    fn manifest_with_blocks() -> Manifest {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+        let binary = Binary::new(&path, &manifest()).unwrap();
+        let blocks = binary.blocks(0x400).unwrap().to_toml();
+
+        let toml = format!(
+            r#"
+            [[version]]
+            name = "Blocks"
+            sha256 = "{}"
+            {}
+            [[version.site]]
+            name = "ratio"
+            offset = {}
+            original = "3A 46 71"
+            "#,
+            hex::encode(binary.patch.sha2()),
+            blocks,
+            RATIO_OFFSET
+        );
+        Manifest::parse(&toml).unwrap()
+    }
+
+    #[test]
+    fn blocks_compute() {
+        let site = Site {
+            name: "ratio".to_owned(),
+            offset: 2,
+            original: vec![0, 0],
+            allowed: vec![],
+            owner: None,
+        };
+        let original = Blocks::compute(&[0; 8], &[], 4);
+        assert_eq!(original.hashes.len(), 2);
+        assert_eq!(original.hashes[0], original.hashes[1]);
+
+        // patch sites are masked
+        let patched = Blocks::compute(&[0, 0, 1, 1, 0, 0, 0, 0], &[site], 4);
+        assert!(patched.differing(&original).is_empty());
+
+        let modified = Blocks::compute(&[0, 0, 0, 0, 0, 1, 0, 0, 0], &[], 4);
+        assert_eq!(modified.differing(&original), [1, 2]);
+    }
+
+    #[test]
+    fn blocks_report() {
+        let with_blocks = manifest_with_blocks();
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut data = binary();
+        data[RATIO_OFFSET..RATIO_OFFSET + 3].copy_from_slice(&[1, 2, 3]);
+        let path = write_binary(dir.path(), &data);
+        let report = BlockReport::new(&path, &with_blocks, &[]).unwrap();
+        assert!(report.is_intact());
+        assert_eq!(report.sites[0].1, SiteState::Other(vec![1, 2, 3]));
+
+        data[0x410] = 0xCC;
+        data[0x800..0xC00].fill(0);
+        let path = write_binary(dir.path(), &data);
+        let report = BlockReport::new(&path, &with_blocks, &[]).unwrap();
+        assert_eq!(
+            report.blocks,
+            [(1, BlockState::Modified), (2, BlockState::Zeroed)]
+        );
+        assert!(report
+            .to_string()
+            .contains("0x00000400..0x00000800: modified"));
+
+        assert!(matches!(
+            BlockReport::new(&path, &manifest(), &[]),
+            Err(ErrorCode::BlocksUnknown(_))
+        ));
+
+        // without blocks in the manifest, they are computed from a copy of the same version
+        let reference = dir.path().join("eracer.exe.bak");
+        std::fs::write(&reference, binary()).unwrap();
+        let report =
+            BlockReport::new(&path, &manifest(), std::slice::from_ref(&reference)).unwrap();
+        assert_eq!(report.reference, Some(reference));
+        assert_eq!(report.blocks, [(0, BlockState::Modified)]);
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Binary, Manifest};
+use crate::{error_code::ErrorCode, hex, pe::PeImage};
+
+const CACHE_DIR: &str = "eracer-config";
+const CACHE_FILE: &str = "cache.json";
+/// Entries of the least recently stored binaries are dropped beyond this count
+const CACHE_LIMIT: usize = 16;
+
+/// Identifies the state of a file without reading it: any modification changes one of the fields
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    path: PathBuf,
+    size: u64,
+    /// modification time as seconds and nanoseconds since the UNIX epoch
+    mtime: (u64, u32),
+    inode: Option<u64>,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> Option<Self> {
+        let path = path.canonicalize().ok()?;
+        let metadata = path.metadata().ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        #[cfg(unix)]
+        let inode = Some(std::os::unix::fs::MetadataExt::ino(&metadata));
+        #[cfg(not(unix))]
+        let inode = None;
+
+        Some(Self {
+            path,
+            size: metadata.len(),
+            mtime: (mtime.as_secs(), mtime.subsec_nanos()),
+            inode,
+        })
+    }
+}
+
+/// The detected version and bytes of its sites
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    #[serde(flatten)]
+    fingerprint: Fingerprint,
+    sha256: String,
+    values: Vec<String>,
+}
+
+/// Results of `Binary::new` stored in the user's config dir, so the binary is hashed only when it
+/// is changed. The cache is best-effort: it is empty if it cannot be read and is not saved if it
+/// cannot be written.
+#[derive(Debug)]
+pub struct Cache {
+    path: Option<PathBuf>,
+    entries: Vec<Entry>,
+}
+
+impl Cache {
+    /// The cache of the current user (`%APPDATA%` or `$XDG_CONFIG_HOME`, `~/.config`)
+    pub fn open() -> Self {
+        Self::at(config_dir().map(|dir| dir.join(CACHE_DIR).join(CACHE_FILE)))
+    }
+
+    /// A cache that is not saved
+    pub fn in_memory() -> Self {
+        Self::at(None)
+    }
+
+    fn at(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_deref()
+            .and_then(|path| File::open(path).ok())
+            .and_then(|file| serde_json::from_reader(file).ok())
+            .unwrap_or_default();
+
+        Self { path, entries }
+    }
+
+    /// Restores the binary at `path` if it has not been changed since it was stored
+    pub(super) fn lookup(&self, path: &Path, manifest: &Manifest) -> Option<Binary> {
+        let fingerprint = Fingerprint::of(path)?;
+        let entry = self.entries.iter().find(|e| e.fingerprint == fingerprint)?;
+        let sha2 = hex::parse(&entry.sha256).ok()?;
+
+        let mut file = File::open(path).ok()?;
+        let pe = PeImage::read(&mut file).ok();
+        let patch = manifest
+            .patches(pe.as_ref())
+            .into_iter()
+            .find(|p| p.sha2()[..] == sha2[..])?;
+
+        let values = entry
+            .values
+            .iter()
+            .map(|value| hex::parse(value).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let sites = patch.sites();
+        if sites.len() != values.len()
+            || sites
+                .iter()
+                .zip(&values)
+                .any(|(s, v)| s.original.len() != v.len())
+        {
+            return None;
+        }
+
+        Binary::with_patch(path, patch, values, pe.map(|pe| pe.timestamp)).ok()
+    }
+
+    /// Remembers the current state of `binary`. Call it after the binary is modified.
+    pub fn store(&mut self, binary: &Binary) {
+        let fingerprint = match Fingerprint::of(&binary.path) {
+            Some(fingerprint) => fingerprint,
+            None => return,
+        };
+
+        self.entries
+            .retain(|e| e.fingerprint.path != fingerprint.path);
+        self.entries.push(Entry {
+            fingerprint,
+            sha256: hex::encode(binary.patch.sha2()),
+            values: binary.values.iter().map(|v| hex::encode(v)).collect(),
+        });
+
+        if self.entries.len() > CACHE_LIMIT {
+            self.entries.drain(..self.entries.len() - CACHE_LIMIT);
+        }
+
+        self.save();
+    }
+
+    fn save(&self) {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return,
+        };
+
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+
+        if let Ok(content) = serde_json::to_string_pretty(&self.entries) {
+            let _ = std::fs::write(path, content);
+        }
+    }
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let var = |name| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+
+    if cfg!(windows) {
+        var("APPDATA")
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+    }
+}
+
+impl Binary {
+    /// `Binary::new` that takes the result from `cache` if the file has not been changed since
+    /// the last run
+    pub fn new_cached(
+        path: &Path,
+        manifest: &Manifest,
+        cache: &mut Cache,
+    ) -> Result<Self, ErrorCode> {
+        if let Some(binary) = cache.lookup(path, manifest) {
+            return Ok(binary);
+        }
+
+        let binary = Self::new(path, manifest)?;
+        cache.store(&binary);
+        Ok(binary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::{patch::test::*, ratio::Ratio};
+
+    fn set_mtime(path: &Path, mtime: SystemTime) {
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(mtime).unwrap();
+    }
+
+    #[test]
+    fn cache_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+        let cache_path = dir.path().join("config").join(CACHE_FILE);
+        let manifest = manifest();
+
+        let mut cache = Cache::at(Some(cache_path.clone()));
+        assert!(cache.lookup(&path, &manifest).is_none());
+        let binary = Binary::new_cached(&path, &manifest, &mut cache).unwrap();
+        assert!(cache_path.exists());
+
+        // the file is not hashed again while its fingerprint is the same
+        let mtime = path.metadata().unwrap().modified().unwrap();
+        let mut data = crate::patch::test::binary();
+        data[RATIO_OFFSET..RATIO_OFFSET + 3].copy_from_slice(&Ratio::new(16, 9).unwrap().hex());
+        std::fs::write(&path, &data).unwrap();
+        set_mtime(&path, mtime);
+
+        let cache = Cache::at(Some(cache_path.clone()));
+        let cached = cache.lookup(&path, &manifest).unwrap();
+        assert_eq!(cached.patch.name(), binary.patch.name());
+        assert_eq!(cached.values, binary.values);
+
+        // a changed mtime invalidates the entry
+        set_mtime(&path, mtime + Duration::from_secs(1));
+        assert!(cache.lookup(&path, &manifest).is_none());
+    }
+
+    #[test]
+    fn cache_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+        let manifest = manifest();
+        let mut cache = Cache::at(Some(dir.path().join(CACHE_FILE)));
+
+        let ratio = Ratio::new(16, 10).unwrap();
+        let mut binary = Binary::new_cached(&path, &manifest, &mut cache).unwrap();
+        binary.set_ratio(ratio).unwrap();
+        cache.store(&binary);
+        assert_eq!(cache.entries.len(), 1);
+
+        let cached = Cache::at(Some(dir.path().join(CACHE_FILE)))
+            .lookup(&path, &manifest)
+            .unwrap();
+        assert_eq!(cached.aspect().known(), Some(ratio));
+
+        // a damaged cache is ignored
+        std::fs::write(dir.path().join(CACHE_FILE), "{").unwrap();
+        assert!(Cache::at(Some(dir.path().join(CACHE_FILE)))
+            .entries
+            .is_empty());
+    }
+}
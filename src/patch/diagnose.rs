@@ -0,0 +1,383 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{Binary, Manifest, Site, RATIO_SITE};
+use crate::{error_code::ErrorCode, hex, pe::PeImage, ratio::Aspect};
+
+/// Regions of a comparison shown in the report, the rest are only counted
+const SHOWN_REGIONS: usize = 8;
+/// A binary is considered modified by another mod (not a different release) if at most this part
+/// of its bytes differ from a known version of the same size
+const MOD_DIFF_RATIO: usize = 100;
+
+/// Contents of a patch site in the diagnosed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum SiteState {
+    Original,
+    /// one of `Site::allowed` values or a known aspect ratio
+    Patched(Vec<u8>),
+    Other(Vec<u8>),
+    /// the site is beyond the end of the file
+    Outside,
+}
+
+impl SiteState {
+    pub(super) fn of(site: &Site, data: &[u8]) -> Self {
+        let value = match data.get(site.range()) {
+            Some(value) => value,
+            None => return SiteState::Outside,
+        };
+
+        let known_ratio = || {
+            site.name == RATIO_SITE
+                && <[u8; 3]>::try_from(value).is_ok_and(|hex| Aspect::from(hex).known().is_some())
+        };
+
+        if value == site.original {
+            SiteState::Original
+        } else if (!site.allowed.is_empty() && site.accepts(value)) || known_ratio() {
+            SiteState::Patched(value.to_vec())
+        } else {
+            SiteState::Other(value.to_vec())
+        }
+    }
+
+    fn is_plausible(&self) -> bool {
+        matches!(self, SiteState::Original | SiteState::Patched(_))
+    }
+}
+
+impl Display for SiteState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SiteState::Original => write!(f, "original"),
+            SiteState::Patched(value) => write!(f, "patched ({:02X?})", value),
+            SiteState::Other(value) => write!(f, "unexpected ({:02X?})", value),
+            SiteState::Outside => write!(f, "beyond the end of the file"),
+        }
+    }
+}
+
+/// Byte-by-byte comparison with a local copy of a known version (e.g. the backup)
+#[derive(Debug)]
+struct Diff {
+    reference: PathBuf,
+    bytes: usize,
+    /// offset and length of each differing region
+    regions: Vec<(usize, usize)>,
+}
+
+impl Diff {
+    /// Sites of both files are put back to original bytes, so patched sites are not counted
+    fn new(reference: &Path, sites: &[Site], data: &[u8]) -> Option<Self> {
+        let mut theirs = std::fs::read(reference).ok()?;
+        let mut ours = data.to_vec();
+        for site in sites {
+            for bytes in [&mut theirs, &mut ours] {
+                if let Some(range) = bytes.get_mut(site.range()) {
+                    range.copy_from_slice(&site.original);
+                }
+            }
+        }
+
+        let mut regions: Vec<(usize, usize)> = vec![];
+        let common = ours.len().min(theirs.len());
+        for offset in (0..common).filter(|&i| ours[i] != theirs[i]) {
+            match regions.last_mut() {
+                Some((start, len)) if *start + *len == offset => *len += 1,
+                _ => regions.push((offset, 1)),
+            }
+        }
+
+        let longer = ours.len().max(theirs.len());
+        if longer > common {
+            regions.push((common, longer - common));
+        }
+
+        Some(Self {
+            reference: reference.to_owned(),
+            bytes: regions.iter().map(|(_, len)| len).sum(),
+            regions,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct VersionReport {
+    name: String,
+    /// known size of the version
+    size: Option<u64>,
+    /// `None` if the sites of the version cannot be placed in the file
+    sites: Option<Vec<(String, SiteState)>>,
+    diff: Option<Diff>,
+}
+
+impl VersionReport {
+    /// How much the file looks like this version, the higher the closer
+    fn score(&self, size: u64) -> usize {
+        let sites = self.sites.iter().flatten();
+        let plausible = sites.filter(|(_, state)| state.is_plausible()).count();
+        let size = self.size.map_or(0, |s| if s == size { 2 } else { 0 });
+
+        plausible + size
+    }
+}
+
+/// Why a binary is not recognized as any known version: the file itself and its comparison with
+/// each version of the manifest
+#[derive(Debug)]
+pub struct Report {
+    path: PathBuf,
+    size: u64,
+    sha2: [u8; 32],
+    pe: Option<PeImage>,
+    /// the version the file is recognized as, if any
+    recognized: Option<String>,
+    versions: Vec<VersionReport>,
+}
+
+impl Report {
+    /// `references` are local copies of known versions (the backup is used automatically) to
+    /// compare the file with byte by byte
+    pub fn new(
+        path: &Path,
+        manifest: &Manifest,
+        references: &[PathBuf],
+    ) -> Result<Self, ErrorCode> {
+        let data = std::fs::read(path)
+            .map_err(|_| ErrorCode::NotFoundBinary(path.to_string_lossy().to_string()))?;
+        let pe = PeImage::parse(&data).ok();
+        let recognized = Binary::new(path, manifest)
+            .ok()
+            .map(|b| b.patch.name().to_owned());
+
+        let references = references
+            .iter()
+            .filter_map(|path| Some((path, Binary::new(path, manifest).ok()?)))
+            .collect::<Vec<_>>();
+
+        let patches = manifest.patches(pe.as_ref());
+        let versions = manifest
+            .versions()
+            .map(|(name, sha2, size)| {
+                let patch = patches.iter().find(|p| p.sha2() == sha2);
+                let sites = patch.map(|p| {
+                    p.sites()
+                        .iter()
+                        .map(|site| (site.name.clone(), SiteState::of(site, &data)))
+                        .collect()
+                });
+
+                let diff = references
+                    .iter()
+                    .find(|(_, binary)| binary.patch.sha2() == sha2)
+                    .and_then(|(path, binary)| Diff::new(path, binary.patch.sites(), &data));
+
+                VersionReport {
+                    name: name.to_owned(),
+                    size,
+                    sites,
+                    diff,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            path: path.to_owned(),
+            size: data.len() as u64,
+            sha2: Sha256::digest(&data).into(),
+            pe,
+            recognized,
+            versions,
+        })
+    }
+
+    /// The versions with the fewest differing bytes, or the ones whose size and sites match best.
+    /// Releases that share their sites (e.g. a language and a Zoom edition) tie, so all of them are
+    /// returned.
+    fn closest(&self) -> Vec<&VersionReport> {
+        let compared = self
+            .versions
+            .iter()
+            .filter_map(|v| Some((v, v.diff.as_ref()?.bytes)))
+            .collect::<Vec<_>>();
+        if let Some(best) = compared.iter().map(|(_, bytes)| *bytes).min() {
+            return compared
+                .into_iter()
+                .filter(|(_, bytes)| *bytes == best)
+                .map(|(version, _)| version)
+                .collect();
+        }
+
+        let best = self.versions.iter().map(|v| v.score(self.size)).max();
+        self.versions
+            .iter()
+            .filter(|v| v.score(self.size) > 0 && Some(v.score(self.size)) == best)
+            .collect()
+    }
+
+    fn verdict(&self) -> String {
+        if let Some(ref name) = self.recognized {
+            return format!("the file is recognized as `{}`", name);
+        }
+
+        if self.pe.is_none() {
+            return "a wrong file: it is not a Windows executable".to_owned();
+        }
+
+        let closest = self.closest();
+        let names = closest
+            .iter()
+            .map(|v| format!("`{}`", v.name))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        match closest.first() {
+            Some(version) => match version.diff {
+                Some(ref diff) if diff.bytes * MOD_DIFF_RATIO <= self.size as usize
+                    && closest
+                        .iter()
+                        .all(|v| v.size.is_none_or(|size| size == self.size)) =>
+                {
+                    format!("{} modified by another mod", names)
+                }
+                Some(_) => format!("a different release (the closest is {})", names),
+                None => format!(
+                    "probably {} modified by another mod or a different release; pass an original {} via `--reference` to compare byte by byte",
+                    names, names
+                ),
+            },
+            None => "a different release or a wrong file".to_owned(),
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "File: {:?}", self.path)?;
+        writeln!(f, "Size: {} bytes", self.size)?;
+        writeln!(f, "sha256: {}", hex::encode(&self.sha2))?;
+        match self.pe {
+            Some(ref pe) => writeln!(f, "PE timestamp: {:#010X}", pe.timestamp)?,
+            None => writeln!(f, "PE timestamp: none (not a PE image)")?,
+        }
+
+        writeln!(f, "\r\nKnown versions:")?;
+        for version in &self.versions {
+            writeln!(f, "  * {}", version.name)?;
+
+            match version.size {
+                Some(size) if size == self.size => writeln!(f, "      size: matches")?,
+                Some(size) => writeln!(f, "      size: {} bytes expected", size)?,
+                None => writeln!(f, "      size: unknown")?,
+            }
+
+            match version.sites {
+                Some(ref sites) => {
+                    for (name, state) in sites {
+                        writeln!(f, "      site `{}`: {}", name, state)?;
+                    }
+                }
+                None => writeln!(f, "      sites: cannot be placed in this file")?,
+            }
+
+            match version.diff {
+                Some(ref diff) => {
+                    writeln!(
+                        f,
+                        "      compared with {:?}: {} bytes differ in {} regions",
+                        diff.reference,
+                        diff.bytes,
+                        diff.regions.len()
+                    )?;
+                    for (offset, len) in diff.regions.iter().take(SHOWN_REGIONS) {
+                        writeln!(f, "        {:#010X}: {} bytes", offset, len)?;
+                    }
+                    if diff.regions.len() > SHOWN_REGIONS {
+                        writeln!(f, "        ...")?;
+                    }
+                }
+                None => writeln!(f, "      compared: no local copy of this version")?,
+            }
+        }
+
+        writeln!(f, "\r\nVerdict: {}", self.verdict())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::patch::test::*;
+
+    #[test]
+    fn diagnose_other_mod() {
+        let dir = tempfile::tempdir().unwrap();
+        let reference = dir.path().join("original.exe");
+        std::fs::write(&reference, binary()).unwrap();
+
+        let mut data = binary();
+        data[0x400..0x404].copy_from_slice(&[0x90; 4]);
+        data[0x900] ^= 0xFF;
+        let path = write_binary(dir.path(), &data);
+
+        let report = Report::new(&path, &manifest(), &[reference]).unwrap();
+        let diff = report.versions[0].diff.as_ref().unwrap();
+        assert_eq!(diff.bytes, 5);
+        assert_eq!(diff.regions, [(0x400, 4), (0x900, 1)]);
+        assert_eq!(report.closest()[0].name, "Test");
+        assert_eq!(report.verdict(), "`Test` modified by another mod");
+
+        let text = report.to_string();
+        assert!(text.contains("site `ratio`: original"));
+        assert!(text.contains("0x00000400: 4 bytes"));
+    }
+
+    #[test]
+    fn diagnose_without_reference() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut data = binary();
+        data[0x900] ^= 0xFF;
+        let path = write_binary(dir.path(), &data);
+
+        let report = Report::new(&path, &manifest(), &[]).unwrap();
+        assert_eq!(
+            report.versions[0].sites.as_ref().unwrap()[0].1,
+            SiteState::Original
+        );
+        assert!(report.verdict().starts_with("probably `Test`"));
+
+        std::fs::write(&path, b"not an executable").unwrap();
+        let report = Report::new(&path, &manifest(), &[]).unwrap();
+        assert!(report.verdict().starts_with("a wrong file"));
+        assert!(report.versions[0].sites.is_none());
+
+        let path = write_binary(dir.path(), &binary());
+        let report = Report::new(&path, &manifest(), &[]).unwrap();
+        assert_eq!(report.verdict(), "the file is recognized as `Test`");
+    }
+
+    #[test]
+    fn diagnose_tie() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut data = binary();
+        data[0x900] ^= 0xFF;
+        let path = write_binary(dir.path(), &data);
+
+        // another release with the same sites, like EN 992 and Zoom 992
+        let toml = manifest_toml();
+        let sha2 = hex::encode(&Sha256::digest(binary()));
+        let twin = toml
+            .replace("\"Test\"", "\"Twin\"")
+            .replace(&sha2, &"00".repeat(32));
+        let manifest = Manifest::parse(&(toml + &twin)).unwrap();
+
+        let report = Report::new(&path, &manifest, &[]).unwrap();
+        let closest = report.closest();
+        assert_eq!(closest.len(), 2);
+        assert!(report.verdict().starts_with("probably `Test` or `Twin`"));
+    }
+}
@@ -0,0 +1,853 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::{
+    blocks::{Blocks, BLOCK_HASH_LENGTH, DEFAULT_BLOCK_SIZE},
+    items::PatchItem,
+    Patch, Site, RATIO_SITE,
+};
+use crate::{
+    error_code::ErrorCode,
+    hex,
+    pe::PeImage,
+    ratio::RATIO_ORIGINAL,
+    store::schema::{Kind, Known, Schema},
+};
+
+/// The manifest of versions supported out of the box
+const EMBEDDED: &str = include_str!("patches.toml");
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    #[serde(default)]
+    version: Vec<VersionEntry>,
+    #[serde(default, rename = "mod")]
+    mods: Vec<ModEntry>,
+    #[serde(default)]
+    value: Vec<ValueEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VersionEntry {
+    name: String,
+    sha256: String,
+    size: Option<u64>,
+    /// size of blocks in `blocks` (`DEFAULT_BLOCK_SIZE` if not set)
+    block_size: Option<usize>,
+    /// truncated sha256 of each block of the file
+    #[serde(default)]
+    blocks: Vec<String>,
+    #[serde(default)]
+    site: Vec<SiteEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SiteEntry {
+    name: String,
+    /// offset from the zero-byte of the file, or from the beginning of `section` if it is set
+    offset: Option<u32>,
+    rva: Option<u32>,
+    va: Option<u64>,
+    section: Option<String>,
+    original: String,
+    /// values the site may be set to (any value of the same length if empty)
+    #[serde(default)]
+    allowed: Vec<String>,
+}
+
+/// A value of the game in the registry with a known meaning (see `Schema`)
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ValueEntry {
+    name: String,
+    title: String,
+    /// `REG_SZ`, `REG_DWORD` or `REG_BINARY`
+    #[serde(rename = "type")]
+    kind: String,
+    min: Option<u32>,
+    max: Option<u32>,
+}
+
+/// A known community mod (no-CD, windowed mode and so on) whose regions are masked while
+/// detecting the version, so the ratio can still be patched in a modded binary
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModEntry {
+    name: String,
+    /// names of versions the mod applies to (any version if empty)
+    #[serde(default)]
+    versions: Vec<String>,
+    #[serde(default)]
+    region: Vec<RegionEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegionEntry {
+    offset: Option<u32>,
+    rva: Option<u32>,
+    va: Option<u64>,
+    section: Option<String>,
+    original: String,
+    patched: String,
+}
+
+/// Where a patch site is placed in the binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Location {
+    File(u32),
+    Rva(u32),
+    Va(u64),
+    Section(String, u32),
+}
+
+impl Location {
+    fn new(
+        offset: Option<u32>,
+        rva: Option<u32>,
+        va: Option<u64>,
+        section: Option<String>,
+    ) -> Option<Self> {
+        match (offset, rva, va, section) {
+            (Some(offset), None, None, None) => Some(Location::File(offset)),
+            (None, Some(rva), None, None) => Some(Location::Rva(rva)),
+            (None, None, Some(va), None) => Some(Location::Va(va)),
+            (Some(offset), None, None, Some(section)) => Some(Location::Section(section, offset)),
+            _ => None,
+        }
+    }
+
+    /// Converts the location to an offset from the zero-byte of the file. If the binary is a PE
+    /// image, the site must be placed inside of a section with initialized data.
+    fn resolve(&self, pe: Option<&PeImage>, length: usize) -> Option<usize> {
+        let offset = self.offset(pe)?;
+        match pe {
+            Some(pe) if !pe.is_initialized_data(offset, length) => None,
+            _ => offset.try_into().ok(),
+        }
+    }
+
+    /// Like `resolve`, but for regions of mods, which patch code as often as data: they must only
+    /// be placed inside of the raw data of any section
+    fn resolve_region(&self, pe: Option<&PeImage>, length: usize) -> Option<usize> {
+        let offset = self.offset(pe)?;
+        match pe {
+            Some(pe) if !pe.is_section_data(offset, length) => None,
+            _ => offset.try_into().ok(),
+        }
+    }
+
+    fn offset(&self, pe: Option<&PeImage>) -> Option<u64> {
+        match (self, pe) {
+            (Location::File(offset), _) => Some(*offset as u64),
+            (Location::Rva(rva), Some(pe)) => pe.rva_to_offset(*rva),
+            (Location::Va(va), Some(pe)) => pe.va_to_offset(*va),
+            (Location::Section(name, offset), Some(pe)) => pe.section(name)?.offset(*offset),
+            (_, None) => None,
+        }
+    }
+}
+
+/// A patch site as it is described in the manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SiteItem {
+    name: String,
+    location: Location,
+    original: Vec<u8>,
+    allowed: Vec<Vec<u8>>,
+}
+
+/// A region of a mod as it is described in the manifest
+#[derive(Debug, Clone)]
+struct RegionItem {
+    location: Location,
+    original: Vec<u8>,
+    patched: Vec<u8>,
+}
+
+/// A known mod as it is described in the manifest
+#[derive(Debug, Clone)]
+struct ModItem {
+    name: String,
+    versions: Vec<String>,
+    regions: Vec<RegionItem>,
+}
+
+impl ModItem {
+    /// Regions as sites of `version`, if all of them can be placed in the binary without
+    /// overlapping `sites`
+    fn sites(&self, version: &str, pe: Option<&PeImage>, sites: &[Site]) -> Option<Vec<Site>> {
+        if !self.versions.is_empty() && !self.versions.iter().any(|v| v == version) {
+            return None;
+        }
+
+        let regions = self
+            .regions
+            .iter()
+            .enumerate()
+            .map(|(index, region)| {
+                Some(Site {
+                    name: format!("{}#{}", self.name, index),
+                    offset: region.location.resolve_region(pe, region.original.len())?,
+                    original: region.original.clone(),
+                    allowed: vec![region.patched.clone()],
+                    owner: Some(self.name.clone()),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let overlaps = |a: &Site, b: &Site| a.offset < b.range().end && b.offset < a.range().end;
+        let conflict = regions
+            .iter()
+            .enumerate()
+            .any(|(i, a)| sites.iter().chain(&regions[..i]).any(|b| overlaps(a, b)));
+
+        match conflict {
+            true => None,
+            false => Some(regions),
+        }
+    }
+}
+
+/// A known version as it is described in the manifest
+#[derive(Debug, Clone)]
+struct VersionItem {
+    name: String,
+    sha2: [u8; 32],
+    size: Option<u64>,
+    sites: Vec<SiteItem>,
+    blocks: Option<Blocks>,
+}
+
+impl VersionItem {
+    fn supplement(&mut self, other: VersionItem) -> Result<(), String> {
+        let name = &self.name;
+        if other.sites != self.sites {
+            return Err(format!(
+                "version `{}` is known with other patch sites",
+                name
+            ));
+        }
+        if other.size.is_some() && self.size.is_some() && other.size != self.size {
+            return Err(format!("version `{}` is known with another size", name));
+        }
+        if other.blocks.is_some() && self.blocks.is_some() {
+            return Err(format!("version `{}` already has hashes of blocks", name));
+        }
+        if other.blocks.is_none() && (other.size.is_none() || self.size.is_some()) {
+            return Err(format!("version `{}` is duplicated", name));
+        }
+
+        let size = self.size.or(other.size);
+        let blocks = self.blocks.as_ref().or(other.blocks.as_ref());
+        if let (Some(size), Some(blocks)) = (size, blocks) {
+            if (size as usize).div_ceil(blocks.size) != blocks.hashes.len() {
+                return Err(format!(
+                    "`{}`: {} blocks of {} bytes do not cover the file of {} bytes",
+                    name,
+                    blocks.hashes.len(),
+                    blocks.size,
+                    size
+                ));
+            }
+        }
+
+        self.size = size;
+        if other.blocks.is_some() {
+            self.blocks = other.blocks;
+        }
+        Ok(())
+    }
+}
+
+/// Database of known versions of the binary
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    items: Vec<VersionItem>,
+    mods: Vec<ModItem>,
+    /// described by `[[value]]` entries
+    values: Vec<Known>,
+}
+
+impl Manifest {
+    pub fn embedded() -> Self {
+        Self::parse(EMBEDDED).expect("embedded manifest must be correct")
+    }
+
+    /// Loads the embedded manifest extended by versions from `extra` file (if any)
+    pub fn load(extra: Option<&Path>) -> Result<Self, ErrorCode> {
+        let mut this = Self::embedded();
+
+        if let Some(path) = extra {
+            let content = std::fs::read_to_string(path).map_err(ErrorCode::IO)?;
+            let incorrect =
+                |e| ErrorCode::IncorrectManifest(format!("{}: {}", path.to_string_lossy(), e));
+
+            let other = Self::parse(&content).map_err(incorrect)?;
+            this.extend(other).map_err(incorrect)?;
+        }
+
+        Ok(this)
+    }
+
+    /// Parses a manifest in the format of `patches.toml`:
+    ///
+    /// ```
+    /// use eracer_config::Manifest;
+    ///
+    /// let manifest = Manifest::parse(
+    ///     r#"
+    ///     [[version]]
+    ///     name = "My release"
+    ///     sha256 = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff"
+    ///
+    ///     [[version.site]]
+    ///     name = "ratio"
+    ///     offset = 0x000CAEFC
+    ///     original = "3A 46 71"
+    ///     "#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(manifest.versions().next().unwrap().0, "My release");
+    /// ```
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let file: ManifestFile = toml::from_str(content).map_err(|e| e.to_string())?;
+
+        let mut this = Self {
+            items: vec![],
+            mods: vec![],
+            values: vec![],
+        };
+        for version in file.version {
+            let item = version.into_item()?;
+            this.push(item)?;
+        }
+
+        for entry in file.mods {
+            let item = entry.into_item()?;
+            this.push_mod(item)?;
+        }
+
+        for entry in file.value {
+            let kind = entry.kind.parse::<Kind>()?;
+            this.push_value(Known {
+                name: entry.name.into(),
+                title: entry.title.into(),
+                kind,
+                min: entry.min,
+                max: entry.max,
+            })?;
+        }
+
+        Ok(this)
+    }
+
+    /// Known versions with patch sites resolved against headers of the binary. Versions whose
+    /// sites cannot be placed in the binary or overlap each other are skipped. Regions of known
+    /// mods become extra sites of the versions they apply to; a mod that overlaps sites of the
+    /// version or of a previous mod is skipped for this version.
+    pub(crate) fn patches(&self, pe: Option<&PeImage>) -> Vec<Box<dyn Patch>> {
+        self.items
+            .iter()
+            .filter_map(|item| {
+                let mut sites = item
+                    .sites
+                    .iter()
+                    .map(|site| {
+                        Some(Site {
+                            name: site.name.clone(),
+                            offset: site.location.resolve(pe, site.original.len())?,
+                            original: site.original.clone(),
+                            allowed: site.allowed.clone(),
+                            owner: None,
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                sites.sort_by_key(|site| site.offset);
+                if sites.windows(2).any(|w| w[0].range().end > w[1].offset) {
+                    return None;
+                }
+
+                for item_mod in &self.mods {
+                    if let Some(regions) = item_mod.sites(&item.name, pe, &sites) {
+                        sites.extend(regions);
+                    }
+                }
+                sites.sort_by_key(|site| site.offset);
+
+                let patch = PatchItem {
+                    name: item.name.clone(),
+                    sha2: item.sha2,
+                    size: item.size,
+                    sites,
+                    blocks: item.blocks.clone(),
+                };
+
+                Some(Box::new(patch) as Box<dyn Patch>)
+            })
+            .collect()
+    }
+
+    /// Values of the game with a known meaning: `schema::KNOWN` and the ones described by
+    /// `[[value]]` entries
+    pub fn schema(&self) -> Schema {
+        let mut schema = Schema::default();
+        for known in &self.values {
+            schema
+                .push(known.clone())
+                .expect("values are checked when they are added");
+        }
+        schema
+    }
+
+    /// Name, sha256 and size (if known) of each version
+    pub fn versions(&self) -> impl Iterator<Item = (&str, &[u8; 32], Option<u64>)> {
+        self.items
+            .iter()
+            .map(|item| (item.name.as_str(), &item.sha2, item.size))
+    }
+
+    /// Adds versions of `other`. An entry of an already known version (the same name, sha256 and
+    /// sites) supplements its `size` and `blocks` instead, e.g. the output of `hash-blocks`.
+    fn extend(&mut self, other: Self) -> Result<(), String> {
+        other.items.into_iter().try_for_each(|item| {
+            match self
+                .items
+                .iter_mut()
+                .find(|known| known.name == item.name && known.sha2 == item.sha2)
+            {
+                Some(known) => known.supplement(item),
+                None => self.push(item),
+            }
+        })?;
+        other
+            .mods
+            .into_iter()
+            .try_for_each(|item| self.push_mod(item))?;
+        other
+            .values
+            .into_iter()
+            .try_for_each(|known| self.push_value(known))
+    }
+
+    fn push_value(&mut self, known: Known) -> Result<(), String> {
+        self.schema().push(known.clone())?;
+        self.values.push(known);
+        Ok(())
+    }
+
+    fn push_mod(&mut self, item: ModItem) -> Result<(), String> {
+        if self.mods.iter().any(|m| m.name == item.name) {
+            return Err(format!("mod `{}` is duplicated", item.name));
+        }
+
+        self.mods.push(item);
+        Ok(())
+    }
+
+    fn push(&mut self, item: VersionItem) -> Result<(), String> {
+        if let Some(known) = self.items.iter().find(|i| i.sha2 == item.sha2) {
+            return Err(format!(
+                "version `{}` has the same sha256 as `{}`",
+                item.name, known.name
+            ));
+        }
+
+        self.items.push(item);
+        Ok(())
+    }
+}
+
+impl VersionEntry {
+    fn into_item(self) -> Result<VersionItem, String> {
+        let name = self.name;
+
+        let sha2 = hex::parse(&self.sha256)?
+            .try_into()
+            .map_err(|_| format!("`{}`: sha256 must be 32 bytes long", name))?;
+
+        let mut sites: Vec<SiteItem> = vec![];
+        for site in self.site {
+            if sites.iter().any(|s| s.name == site.name) {
+                return Err(format!("`{}`: duplicated patch site `{}`", name, site.name));
+            }
+
+            let original = hex::parse(&site.original)?;
+            if original.is_empty() {
+                return Err(format!(
+                    "`{}`: patch site `{}` must not be empty",
+                    name, site.name
+                ));
+            }
+
+            if site.name == RATIO_SITE && original != RATIO_ORIGINAL {
+                return Err(format!(
+                    "`{}`: patch site `{}` must contain {:02X?} originally",
+                    name, site.name, RATIO_ORIGINAL
+                ));
+            }
+
+            let allowed = site
+                .allowed
+                .iter()
+                .map(|value| hex::parse(value))
+                .collect::<Result<Vec<_>, _>>()?;
+            if allowed.iter().any(|value| value.len() != original.len()) {
+                return Err(format!(
+                    "`{}`: allowed values of patch site `{}` must be {} bytes long",
+                    name,
+                    site.name,
+                    original.len()
+                ));
+            }
+
+            let location = Location::new(site.offset, site.rva, site.va, site.section).ok_or_else(|| {
+                format!(
+                    "`{}`: patch site `{}` must have exactly one of `offset`, `rva`, `va` or `section` + `offset`",
+                    name, site.name
+                )
+            })?;
+
+            sites.push(SiteItem {
+                name: site.name,
+                location,
+                original,
+                allowed,
+            });
+        }
+
+        if !sites.iter().any(|site| site.name == RATIO_SITE) {
+            return Err(format!(
+                "`{}`: patch site `{}` is missing",
+                name, RATIO_SITE
+            ));
+        }
+
+        let blocks = match (self.block_size, self.blocks.is_empty()) {
+            (None, true) => None,
+            (Some(_), true) => return Err(format!("`{}`: `block_size` without `blocks`", name)),
+            (Some(0), false) => return Err(format!("`{}`: `block_size` must not be 0", name)),
+            (size, false) => {
+                let size = size.unwrap_or(DEFAULT_BLOCK_SIZE);
+                let hashes = self
+                    .blocks
+                    .iter()
+                    .map(|hash| {
+                        hex::parse(hash)?.try_into().map_err(|_| {
+                            format!(
+                                "`{}`: hashes of blocks must be {} bytes long",
+                                name, BLOCK_HASH_LENGTH
+                            )
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+
+                let expected = self.size.map(|s| (s as usize).div_ceil(size));
+                if expected.is_some_and(|count| count != hashes.len()) {
+                    return Err(format!(
+                        "`{}`: {} blocks of {} bytes do not cover the file of {} bytes",
+                        name,
+                        hashes.len(),
+                        size,
+                        self.size.unwrap_or_default()
+                    ));
+                }
+
+                Some(Blocks { size, hashes })
+            }
+        };
+
+        Ok(VersionItem {
+            name,
+            sha2,
+            size: self.size,
+            sites,
+            blocks,
+        })
+    }
+}
+
+impl ModEntry {
+    fn into_item(self) -> Result<ModItem, String> {
+        let name = self.name;
+        if self.region.is_empty() {
+            return Err(format!("mod `{}` has no regions", name));
+        }
+
+        let regions = self
+            .region
+            .into_iter()
+            .map(|region| {
+                let original = hex::parse(&region.original)?;
+                let patched = hex::parse(&region.patched)?;
+                if original.is_empty() || original.len() != patched.len() {
+                    return Err(format!(
+                        "mod `{}`: `original` and `patched` of a region must be of the same non-zero length",
+                        name
+                    ));
+                }
+
+                let location = Location::new(region.offset, region.rva, region.va, region.section)
+                    .ok_or_else(|| {
+                        format!(
+                            "mod `{}`: a region must have exactly one of `offset`, `rva`, `va` or `section` + `offset`",
+                            name
+                        )
+                    })?;
+
+                Ok(RegionItem {
+                    location,
+                    original,
+                    patched,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(ModItem {
+            name,
+            versions: self.versions,
+            regions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::Value;
+
+    const EXTRA: &str = r#"
+        [[version]]
+        name = "GOG"
+        sha256 = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff"
+        size = 1015808
+
+        [[version.site]]
+        name = "ratio"
+        offset = 0x000CAF00
+        original = "3A4671"
+    "#;
+
+    #[test]
+    fn manifest_embedded() {
+        let manifest = Manifest::embedded();
+        let patches = manifest.patches(None);
+        assert_eq!(patches.len(), 3);
+        assert_eq!(patches[0].name(), "RU 1008 KB");
+        assert_eq!(patches[0].sites()[0].offset, 0x000C7ED8);
+        assert_eq!(patches[1].sha2()[..2], [0xFC, 0x4F]);
+    }
+
+    #[test]
+    fn manifest_extend() {
+        let mut manifest = Manifest::embedded();
+        manifest.extend(Manifest::parse(EXTRA).unwrap()).unwrap();
+
+        let patches = manifest.patches(None);
+        assert_eq!(patches.len(), 4);
+        assert_eq!(patches[3].name(), "GOG");
+        assert_eq!(patches[3].size(), Some(1015808));
+        assert_eq!(patches[3].sites()[0].offset, 0x000CAF00);
+
+        assert!(manifest.extend(Manifest::parse(EXTRA).unwrap()).is_err());
+
+        // hashes of blocks of a bundled version, as printed by `hash-blocks`
+        let ru = r#"
+            [[version]]
+            name = "RU 1008 KB"
+            sha256 = "7a3bf773cb620b8c547ee04b40cd43a38d9c969b925e9beca5363b40588f9380"
+            size = 0xFC000
+            block_size = 0x80000
+            blocks = ["0011223344556677", "8899AABBCCDDEEFF"]
+
+            [[version.site]]
+            name = "ratio"
+            offset = 0x000C7ED8
+            original = "3A 46 71"
+        "#;
+        manifest.extend(Manifest::parse(ru).unwrap()).unwrap();
+        let patches = manifest.patches(None);
+        assert_eq!(patches.len(), 4);
+        assert_eq!(patches[0].size(), Some(0xFC000));
+        assert_eq!(patches[0].blocks().unwrap().hashes.len(), 2);
+
+        assert!(manifest.extend(Manifest::parse(ru).unwrap()).is_err());
+        let mut other = Manifest::embedded();
+        let moved = Manifest::parse(&ru.replace("C7ED8", "C7ED0")).unwrap();
+        assert!(other.extend(moved).is_err());
+    }
+
+    #[test]
+    fn manifest_incorrect() {
+        assert!(Manifest::parse(&EXTRA.replace("3A4671", "3A4672")).is_err());
+        assert!(Manifest::parse(&EXTRA.replace("\"ratio\"", "\"fov\"")).is_err());
+        assert!(Manifest::parse(&format!("{}{}", EXTRA, FOV.replace("fov", "ratio"))).is_err());
+        assert!(Manifest::parse(&EXTRA.replace("00112233", "0011223")).is_err());
+        assert!(Manifest::parse(&EXTRA.replace("00112233", "")).is_err());
+        assert!(Manifest::parse(&EXTRA.replace("size", "length")).is_err());
+        assert!(Manifest::parse(&EXTRA.replace("offset", "rva = 0x2010\noffset")).is_err());
+    }
+
+    const FOV: &str = r#"
+        [[version.site]]
+        name = "fov"
+        offset = 0x000CAF10
+        original = "00 00 80 3F"
+        allowed = ["00 00 A0 3F", "00 00 C0 3F"]
+    "#;
+
+    #[test]
+    fn manifest_sites() {
+        let manifest = Manifest::parse(&format!("{}{}", EXTRA, FOV)).unwrap();
+        let patches = manifest.patches(None);
+        let sites = patches[0].sites();
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[1].name, "fov");
+        assert_eq!(sites[1].range(), 0x000CAF10..0x000CAF14);
+        assert_eq!(sites[1].allowed[1], [0x00, 0x00, 0xC0, 0x3F]);
+
+        assert!(Manifest::parse(&format!("{}{}", EXTRA, FOV.replace("A0 3F", "A0"))).is_err());
+        assert!(Manifest::parse(&format!(
+            "{}{}",
+            EXTRA,
+            FOV.replace("\"00 00 80 3F\"", "\"\"")
+        ))
+        .is_err());
+
+        // sites must not overlap
+        let manifest =
+            Manifest::parse(&format!("{}{}", EXTRA, FOV.replace("CAF10", "CAF02"))).unwrap();
+        assert!(manifest.patches(None).is_empty());
+    }
+
+    #[test]
+    fn manifest_blocks() {
+        let blocks = "blocks = [\"0011223344556677\", \"8899AABBCCDDEEFF\"]\n[[version.site]]";
+        let large = format!("block_size = 0x80000\n{}", blocks);
+        let manifest = Manifest::parse(&EXTRA.replacen("[[version.site]]", &large, 1)).unwrap();
+        let patches = manifest.patches(None);
+        let parsed = patches[0].blocks().unwrap();
+        assert_eq!(parsed.size, 0x80000);
+        assert_eq!(parsed.hashes[1][0], 0x88);
+
+        assert!(Manifest::embedded().patches(None)[0].blocks().is_none());
+        let short = "blocks = [\"00\"]\n[[version.site]]";
+        assert!(Manifest::parse(&EXTRA.replacen("[[version.site]]", short, 1)).is_err());
+        // 2 blocks of 4 KiB cannot cover the file
+        assert!(Manifest::parse(&EXTRA.replacen("[[version.site]]", blocks, 1)).is_err());
+    }
+
+    const MODS: &str = r#"
+        [[mod]]
+        name = "No-CD"
+        versions = ["GOG"]
+
+        [[mod.region]]
+        offset = 0x1000
+        original = "74 05"
+        patched = "EB 05"
+
+        [[mod.region]]
+        offset = 0x2000
+        original = "75"
+        patched = "90"
+
+        [[mod]]
+        name = "Conflict"
+
+        [[mod.region]]
+        offset = 0x000CAF01
+        original = "00"
+        patched = "01"
+    "#;
+
+    #[test]
+    fn manifest_mods() {
+        let manifest = Manifest::parse(&format!("{}{}", EXTRA, MODS)).unwrap();
+        let patches = manifest.patches(None);
+        let sites = patches[0].sites();
+        assert_eq!(sites.len(), 3);
+        assert_eq!(sites[0].name, "No-CD#0");
+        assert_eq!(sites[0].owner.as_deref(), Some("No-CD"));
+        assert_eq!(sites[0].allowed, [vec![0xEB, 0x05]]);
+        assert_eq!(sites[2].name, "ratio");
+
+        // `versions` limits the mod, a mod without `versions` applies to every version
+        let mut manifest = Manifest::embedded();
+        manifest.extend(Manifest::parse(MODS).unwrap()).unwrap();
+        for patch in manifest.patches(None) {
+            let owners = patch.sites().iter().filter_map(|s| s.owner.as_deref());
+            assert_eq!(owners.collect::<Vec<_>>(), ["Conflict"]);
+        }
+
+        assert!(Manifest::parse(&MODS.replace("\"EB 05\"", "\"EB\"")).is_err());
+        assert!(Manifest::parse(&format!("{}{}", MODS, MODS)).is_err());
+    }
+
+    const VALUES: &str = r#"
+        [[value]]
+        name = "Detail"
+        title = "Level of detail"
+        type = "REG_DWORD"
+        min = 0
+        max = 2
+    "#;
+
+    #[test]
+    fn manifest_values() {
+        let mut manifest = Manifest::embedded();
+        assert_eq!(manifest.schema(), Schema::default());
+
+        manifest.extend(Manifest::parse(VALUES).unwrap()).unwrap();
+        let schema = manifest.schema();
+        assert_eq!(schema.known("DETAIL").unwrap().title, "Level of detail");
+        assert!(schema.check("detail", &Value::U32(2)).is_ok());
+        assert!(schema.check("detail", &Value::U32(3)).is_err());
+        assert!(manifest.extend(Manifest::parse(VALUES).unwrap()).is_err());
+
+        assert!(Manifest::parse(&VALUES.replace("Detail", "PREFERRED WIDTH")).is_err());
+        assert!(Manifest::parse(&VALUES.replace("REG_DWORD", "REG_QWORD")).is_err());
+        assert!(Manifest::parse(&VALUES.replace("REG_DWORD", "REG_SZ")).is_err());
+        assert!(Manifest::parse(&VALUES.replace("max = 2", "max = -1")).is_err());
+        assert!(Manifest::parse(&format!("{}{}", VALUES, VALUES)).is_err());
+    }
+
+    #[test]
+    fn manifest_locations() {
+        let pe = PeImage::parse(&crate::pe::test::image()).unwrap();
+
+        assert_eq!(Location::File(0x810).resolve(Some(&pe), 3), Some(0x810));
+        assert_eq!(Location::File(0x810).resolve(None, 3), Some(0x810));
+        assert_eq!(Location::Rva(0x2010).resolve(Some(&pe), 3), Some(0x810));
+        assert_eq!(Location::Va(0x0040_2010).resolve(Some(&pe), 3), Some(0x810));
+        let section = Location::Section(".data".to_owned(), 0x10);
+        assert_eq!(section.resolve(Some(&pe), 3), Some(0x810));
+
+        // not an initialized data
+        assert_eq!(Location::File(0x410).resolve(Some(&pe), 3), None);
+        assert_eq!(Location::Rva(0x1010).resolve(Some(&pe), 3), None);
+        // PE headers are required
+        assert_eq!(Location::Rva(0x2010).resolve(None, 3), None);
+
+        // regions of mods may patch code, but not headers or beyond the raw data
+        assert_eq!(
+            Location::File(0x410).resolve_region(Some(&pe), 3),
+            Some(0x410)
+        );
+        assert_eq!(
+            Location::Rva(0x1010).resolve_region(Some(&pe), 3),
+            Some(0x410)
+        );
+        assert_eq!(Location::File(0x10).resolve_region(Some(&pe), 3), None);
+        assert_eq!(Location::File(0x7FF).resolve_region(Some(&pe), 3), None);
+
+        let manifest = Manifest::parse(&EXTRA.replace("offset", "rva = 0x2010\n#")).unwrap();
+        assert_eq!(manifest.patches(Some(&pe))[0].sites()[0].offset, 0x810);
+        assert!(manifest.patches(None).is_empty());
+    }
+}
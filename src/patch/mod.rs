@@ -1,23 +1,48 @@
+mod backup;
+mod blocks;
+mod cache;
+mod diagnose;
 mod items;
+mod manifest;
+mod signature;
+mod site;
 
 use std::{
     fmt::Debug,
     fs::{File, OpenOptions},
-    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    io::{Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
 };
 
 use sha2::{Digest, Sha256};
 
+pub use backup::Backup;
+pub use blocks::{BlockReport, Blocks, DEFAULT_BLOCK_SIZE};
+pub use cache::Cache;
+pub use diagnose::Report;
+pub use manifest::Manifest;
+pub use signature::Signature;
+pub use site::Site;
+
 use crate::{
+    atomic::{self, temp_path},
     error_code::ErrorCode,
-    ratio::{Ratio, RATIO_ORIGINAL},
+    pe::PeImage,
+    ratio::{Aspect, Ratio, RATIO_LENGTH},
 };
 
+/// Name of the patch site that holds the aspect ratio, every patch has it
+pub const RATIO_SITE: &str = "ratio";
+
 pub struct Binary {
     path: PathBuf,
     patch: Box<dyn Patch>,
-    aspect: [u8; 3],
+    /// current bytes of each of `Patch::sites`
+    values: Vec<Vec<u8>>,
+    /// index of `RATIO_SITE` in `Patch::sites`
+    ratio: usize,
+    /// link time of the binary from its PE header
+    timestamp: Option<u32>,
 }
 
 impl Debug for Binary {
@@ -25,153 +50,859 @@ impl Debug for Binary {
         f.debug_struct("Binary")
             .field("path", &self.path)
             .field("patch", &self.patch.name())
-            .field("aspect", &Ratio::try_from(&self.aspect))
+            .field("timestamp", &self.timestamp)
+            .field("aspect", &self.aspect())
+            .field("sites", &SitesDebug(self))
+            .field("mods", &self.mods())
             .finish()
     }
 }
 
 impl Binary {
-    pub fn new(path: &Path) -> Result<Self, ErrorCode> {
-        let file = File::open(path).map_err(ErrorCode::IO)?;
-        let mut reader = BufReader::new(file);
-        let (patch, aspect) = get_patch_by_binary(&mut reader).ok_or(
+    pub fn new(path: &Path, manifest: &Manifest) -> Result<Self, ErrorCode> {
+        let mut file = File::open(path).map_err(ErrorCode::IO)?;
+        let size = file.metadata().map_err(ErrorCode::IO)?.len();
+        let pe = PeImage::read(&mut file).ok();
+        file.rewind().map_err(ErrorCode::IO)?;
+
+        let mut patches = manifest
+            .patches(pe.as_ref())
+            .into_iter()
+            .filter(|p| p.size().is_none_or(|s| s == size))
+            .filter(|p| p.site_index(RATIO_SITE).is_some())
+            .collect::<Vec<_>>();
+
+        let candidates = patches.iter().map(|p| p.as_ref()).collect::<Vec<_>>();
+        let (index, values) = get_patch_by_binary(&mut file, &candidates).ok_or(
             ErrorCode::IncorrectHashOfBinary(path.to_string_lossy().to_string()),
         )?;
+        let patch = patches.swap_remove(index);
+
+        Self::with_patch(path, patch, values, pe.map(|pe| pe.timestamp))
+    }
+
+    fn with_patch(
+        path: &Path,
+        patch: Box<dyn Patch>,
+        values: Vec<Vec<u8>>,
+        timestamp: Option<u32>,
+    ) -> Result<Self, ErrorCode> {
+        let ratio = patch
+            .site_index(RATIO_SITE)
+            .filter(|&index| patch.sites()[index].original.len() == RATIO_LENGTH)
+            .ok_or_else(|| ErrorCode::IncorrectHashOfBinary(path.to_string_lossy().to_string()))?;
 
         Ok(Self {
             path: path.to_owned(),
             patch,
-            aspect,
+            values,
+            ratio,
+            timestamp,
         })
     }
 
-    pub fn set_ratio(&mut self, ratio: Ratio) -> Result<(), ErrorCode> {
-        let file = OpenOptions::new()
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The detected version
+    pub fn patch(&self) -> &dyn Patch {
+        self.patch.as_ref()
+    }
+
+    /// Link time of the binary from its PE header
+    pub fn timestamp(&self) -> Option<u32> {
+        self.timestamp
+    }
+
+    pub fn ratio_offset(&self) -> usize {
+        self.patch.sites()[self.ratio].offset
+    }
+
+    pub fn aspect(&self) -> Aspect {
+        let mut hex = [0; RATIO_LENGTH];
+        hex.copy_from_slice(&self.values[self.ratio]);
+        Aspect::from(hex)
+    }
+
+    /// Current bytes of the site named `name`, `None` if the version has no such site
+    pub fn site_value(&self, name: &str) -> Option<&[u8]> {
+        let index = self.patch.site_index(name)?;
+        Some(&self.values[index])
+    }
+
+    /// Contents of the original release (the file with the original bytes of all sites put back)
+    /// and the same contents patched with `ratio`
+    pub fn ratio_patch(&self, ratio: Ratio) -> Result<(Vec<u8>, Vec<u8>), ErrorCode> {
+        let mut source = std::fs::read(&self.path).map_err(ErrorCode::IO)?;
+        for site in self.patch.sites() {
+            source
+                .get_mut(site.range())
+                .ok_or_else(|| {
+                    ErrorCode::IncorrectHashOfBinary(self.path.to_string_lossy().to_string())
+                })?
+                .copy_from_slice(&site.original);
+        }
+
+        let mut target = source.clone();
+        target[self.patch.sites()[self.ratio].range()].copy_from_slice(&ratio.hex());
+
+        Ok((source, target))
+    }
+
+    /// Block hashes of the file with the original bytes of all sites, to be put into the manifest
+    pub fn blocks(&self, size: usize) -> Result<Blocks, ErrorCode> {
+        let data = std::fs::read(&self.path).map_err(ErrorCode::IO)?;
+        Ok(Blocks::compute(&data, self.patch.sites(), size))
+    }
+
+    /// Known mods found in the binary. A mod is listed if any of its regions is patched, with a
+    /// note if only some of them are.
+    pub fn mods(&self) -> Vec<String> {
+        let mut mods: Vec<(&str, usize, usize)> = vec![];
+        let sites = self.patch.sites().iter().zip(&self.values);
+        for (site, value) in sites {
+            let owner = match site.owner {
+                Some(ref owner) => owner.as_str(),
+                None => continue,
+            };
+
+            let patched = (value != &site.original) as usize;
+            match mods.iter_mut().find(|(name, _, _)| *name == owner) {
+                Some((_, count, total)) => {
+                    *count += patched;
+                    *total += 1;
+                }
+                None => mods.push((owner, patched, 1)),
+            }
+        }
+
+        mods.into_iter()
+            .filter(|&(_, count, _)| count > 0)
+            .map(|(name, count, total)| match count == total {
+                true => name.to_owned(),
+                false => format!("{} (partially)", name),
+            })
+            .collect()
+    }
+
+    /// Checks that the whole file is identical to the original release (`Patch::sha2`). Regions of
+    /// known mods are put back to original bytes, so a backup of a modded binary passes.
+    pub fn verify_original(&self) -> Result<(), ErrorCode> {
+        let owned = self
+            .patch
+            .sites()
+            .iter()
+            .filter(|site| site.owner.is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut file = File::open(&self.path).map_err(ErrorCode::IO)?;
+        let mut detector = BinaryDetector::new(&owned);
+        let mut buffer = vec![0; BLOCK_SIZE];
+        loop {
+            let count = file.read(&mut buffer).map_err(ErrorCode::IO)?;
+            if count == 0 {
+                break;
+            }
+
+            detector.update(&buffer[..count]);
+        }
+
+        if detector.finish().map(|(sha2, _)| sha2).as_ref() != Some(self.patch.sha2()) {
+            Err(ErrorCode::RestoredHashMismatch(
+                self.path.to_string_lossy().to_string(),
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// The binary is never modified in place: the patched copy is written into a temporary file
+    /// next to it, flushed to the disk and checked, and only then it replaces the binary. The
+    /// result is verified once again (see `Binary::verify`), its sha256 is returned.
+    pub fn set_ratio(&mut self, ratio: Ratio) -> Result<[u8; 32], ErrorCode> {
+        self.set_site(RATIO_SITE, &ratio.hex())
+    }
+
+    /// Writes `value` into the site named `name` the same way as `Binary::set_ratio` does. The
+    /// value must be accepted by the site (see `Site::allowed`).
+    pub fn set_site(&mut self, name: &str, value: &[u8]) -> Result<[u8; 32], ErrorCode> {
+        let index = self.patch.site_index(name).ok_or_else(|| {
+            ErrorCode::PatchFailed(format!(
+                "`{}` has no patch site `{}`",
+                self.patch.name(),
+                name
+            ))
+        })?;
+
+        let site = &self.patch.sites()[index];
+        if let Some(ref owner) = site.owner {
+            Err(ErrorCode::PatchFailed(format!(
+                "patch site `{}` belongs to mod `{}` and cannot be changed",
+                name, owner
+            )))?;
+        }
+
+        if !site.accepts(value) {
+            Err(ErrorCode::PatchFailed(format!(
+                "{:02X?} is not allowed for patch site `{}`",
+                value, name
+            )))?;
+        }
+
+        let mut values = self.values.clone();
+        values[index] = value.to_vec();
+        let temp = temp_path(&self.path);
+
+        let result = self.write_patched(&temp, index, &values).and_then(|()| {
+            std::fs::rename(&temp, &self.path).map_err(|e| {
+                ErrorCode::PatchFailed(format!(
+                    "cannot replace `{}` by `{}`: {}",
+                    self.path.to_string_lossy(),
+                    temp.to_string_lossy(),
+                    e
+                ))
+            })
+        });
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+
+        result?;
+        self.values = values;
+
+        self.verify()
+    }
+
+    /// Re-reads the file and checks that it is still recognized as the same version and contains
+    /// the expected bytes in all sites. Returns sha256 of the whole file.
+    pub fn verify(&self) -> Result<[u8; 32], ErrorCode> {
+        let path_str = self.path.to_string_lossy().to_string();
+        let failed = |reason: String| ErrorCode::VerificationFailed(path_str.clone(), reason);
+
+        let file = File::open(&self.path).map_err(|e| failed(e.to_string()))?;
+        self.check(file, &self.values)
+            .map_err(|reason| failed(format!("it {}", reason)))?;
+
+        backup::sha256(&self.path)
+    }
+
+    fn write_patched(
+        &self,
+        temp: &Path,
+        index: usize,
+        values: &[Vec<u8>],
+    ) -> Result<(), ErrorCode> {
+        let temp_str = temp.to_string_lossy();
+        let failed = |step: &str, e: std::io::Error| {
+            ErrorCode::PatchFailed(format!("cannot {} `{}`: {}", step, temp_str, e))
+        };
+
+        std::fs::copy(&self.path, temp).map_err(|e| failed("create a temporary file", e))?;
+
+        let mut file = OpenOptions::new()
+            .read(true)
             .write(true)
-            .open(&self.path)
-            .map_err(ErrorCode::IO)?;
+            .open(temp)
+            .map_err(|e| failed("open a temporary file", e))?;
 
-        let mut writer = BufWriter::new(file);
-        writer
-            .seek(SeekFrom::Current(self.patch.ratio_offset() as i64))
-            .map_err(ErrorCode::IO)?;
-        writer.write(ratio.hex()).map_err(ErrorCode::IO)?;
-        self.aspect.copy_from_slice(ratio.hex());
+        file.seek(SeekFrom::Start(self.patch.sites()[index].offset as u64))
+            .and_then(|_| file.write_all(&values[index]))
+            .map_err(|e| failed("write into a temporary file", e))?;
+        file.sync_all()
+            .map_err(|e| failed("flush a temporary file", e))?;
+
+        file.rewind()
+            .map_err(|e| failed("read a temporary file", e))?;
+        self.check(file, values).map_err(|reason| {
+            ErrorCode::PatchFailed(format!("`{}` {} after writing", temp_str, reason))
+        })
+    }
+
+    /// Checks that `file` is recognized as the same version and its sites contain `values`
+    fn check(&self, mut file: File, values: &[Vec<u8>]) -> Result<(), String> {
+        let found = match get_patch_by_binary(&mut file, &[self.patch.as_ref()]) {
+            Some((_, found)) => found,
+            None => return Err(format!("is not recognized as `{}`", self.patch.name())),
+        };
+
+        let sites = self.patch.sites().iter().zip(found.iter().zip(values));
+        for (site, (found, value)) in sites {
+            if found != value {
+                return Err(format!(
+                    "contains {:02X?} instead of {:02X?} in patch site `{}`",
+                    found, value, site.name
+                ));
+            }
+        }
 
         Ok(())
     }
 }
 
-fn get_patch_by_binary<R: Read>(reader: &mut R) -> Option<(Box<dyn Patch>, [u8; 3])> {
-    let mut patch_detectors = get_all_patches().map(|i| BinaryDetector::from(i));
-    let mut buffer = [0; 32];
-    // let mut offset = 0;
+/// Atomically replaces the content of `path` by `data` through a temporary file. Returns sha256 of
+/// the written file.
+pub fn replace_file(path: &Path, data: &[u8]) -> Result<[u8; 32], ErrorCode> {
+    atomic::replace(path, data, ErrorCode::PatchFailed)?;
+    backup::sha256(path)
+}
+
+/// Size of a block the binary is read by while detecting its version
+const BLOCK_SIZE: usize = 0x10000;
 
+/// Returns index of the detected patch in `patches` and the current bytes of its sites. The binary
+/// is read once; patches with the same sites (e.g. releases that differ only in code) share a
+/// single hasher. Regions of known mods must contain either the original or the patched bytes.
+fn get_patch_by_binary<R: Read>(
+    reader: &mut R,
+    patches: &[&dyn Patch],
+) -> Option<(usize, Vec<Vec<u8>>)> {
+    let mut detectors: Vec<BinaryDetector> = vec![];
+    let owners = patches
+        .iter()
+        .map(|patch| {
+            let sites = patch.sites();
+            match detectors.iter().position(|d| d.masks_as(sites)) {
+                Some(index) => index,
+                None => {
+                    detectors.push(BinaryDetector::new(sites));
+                    detectors.len() - 1
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut buffer = vec![0; BLOCK_SIZE];
     loop {
         let count = reader.read(&mut buffer).ok()?;
         if count == 0 {
             break;
         }
 
-        for p in &mut patch_detectors {
-            p.update(&buffer[..count]);
+        for detector in &mut detectors {
+            detector.update(&buffer[..count]);
         }
     }
 
-    patch_detectors
+    let results = detectors
         .into_iter()
-        .map(|i| i.finish())
-        .find(Option::is_some)?
-}
+        .map(BinaryDetector::finish)
+        .collect::<Vec<_>>();
 
-const PATCHES_COUNT: usize = 3;
+    patches
+        .iter()
+        .zip(owners)
+        .enumerate()
+        .find_map(|(index, (patch, owner))| match &results[owner] {
+            Some((sha2, values))
+                if sha2 == patch.sha2() && mods_accepted(patch.sites(), values) =>
+            {
+                Some((index, values.clone()))
+            }
+            _ => None,
+        })
+}
 
-fn get_all_patches() -> [Box<dyn Patch>; PATCHES_COUNT] {
-    [Box::new(items::PatchRU_1008), Box::new(items::PatchEN_992), Box::new(items::Patch_Zoom)]
+fn mods_accepted(sites: &[Site], values: &[Vec<u8>]) -> bool {
+    sites
+        .iter()
+        .zip(values)
+        .filter(|(site, _)| site.owner.is_some())
+        .all(|(site, value)| site.accepts(value))
 }
 
 pub trait Patch {
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
 
     /// sha2 of the original binary file
-    fn sha2(&self) -> &'static [u8; 32];
+    fn sha2(&self) -> &[u8; 32];
+
+    /// size of the binary file in bytes (if known)
+    fn size(&self) -> Option<u64>;
 
-    /// offset from zero-byte of the binary file
-    fn ratio_offset(&self) -> usize;
+    /// patch sites sorted by offset, they do not overlap (see `Manifest::patches` for kinds of
+    /// addresses)
+    fn sites(&self) -> &[Site];
 
-    fn ratio_len(&self) -> usize {
-        crate::ratio::RATIO_LENGTH
+    /// hashes of blocks of the original binary file (if known)
+    fn blocks(&self) -> Option<&Blocks> {
+        None
+    }
+
+    fn site_index(&self, name: &str) -> Option<usize> {
+        self.sites().iter().position(|site| site.name == name)
     }
 }
 
-struct BinaryDetector {
-    patch: Box<dyn Patch>,
+/// Hashes the binary with original bytes of all sites put back and collects the current bytes of
+/// the sites. The binary can be fed in chunks of any size: a site may span any number of chunks.
+struct BinaryDetector<'a> {
+    sites: &'a [Site],
     hasher: Sha256,
-    ratio: Option<[u8; 3]>,
+    values: Vec<Vec<u8>>,
     byte_count: usize,
 }
 
-impl BinaryDetector {
+impl<'a> BinaryDetector<'a> {
+    pub fn new(sites: &'a [Site]) -> Self {
+        BinaryDetector {
+            sites,
+            hasher: Sha256::new(),
+            values: vec![vec![]; sites.len()],
+            byte_count: 0,
+        }
+    }
+
+    /// Whether the hash of this detector is the same as of a detector of `sites`
+    pub fn masks_as(&self, sites: &[Site]) -> bool {
+        self.sites.len() == sites.len()
+            && self
+                .sites
+                .iter()
+                .zip(sites)
+                .all(|(a, b)| a.offset == b.offset && a.original == b.original)
+    }
+
     pub fn update(&mut self, data: &[u8]) {
-        let count = data.len();
-        let ratio_offset = self.patch.ratio_offset();
-        let ratio_len = self.patch.ratio_len();
-
-        // We want to calc hash of the binary with original ratio bytes, thus we replace ratio bytes
-        // from original binary (hardcoded as `RATIO_ORIGINAL`) on the fly. This works if and only if
-        // ALL ratio bytes is included in the same buffer. In our case, all works ok with hardcoded
-        // values in all `Patch::ratio_offset` methods and a 32-byte buffer.
-        //
-        //   TODO: checks for split ratio bytes into different buffers to possible changing
-        // buffer's length without any fear.
-        if ratio_offset > self.byte_count && ratio_offset + ratio_len < self.byte_count + count {
-            // println!("{}\r\n{:X?}", &self.patch.name(), &data[..]);
-            // println!(
-            //     "{}\r\n{:X?}",
-            //     &self.patch.name(),
-            //     &data[..ratio_offset - self.byte_count]
-            // );
-            // println!(
-            //     "{}\r\n{:X?}",
-            //     &self.patch.name(),
-            //     &data[ratio_offset - self.byte_count + ratio_len..]
-            // );
-
-            self.hasher.update(&data[..ratio_offset - self.byte_count]);
-            self.hasher.update(RATIO_ORIGINAL);
-            self.hasher
-                .update(&data[ratio_offset - self.byte_count + ratio_len..]);
-            self.ratio = Some({
-                let mut copied = [0, 0, 0];
-                copied.copy_from_slice(
-                    &data[ratio_offset - self.byte_count
-                        ..ratio_offset - self.byte_count + ratio_len],
-                );
-                copied
-            });
-        } else {
-            self.hasher.update(data);
-        }
-
-        self.byte_count += count;
-    }
-
-    pub fn finish(self) -> Option<(Box<dyn Patch>, [u8; 3])> {
-        if &self.hasher.finalize()[..] == self.patch.sha2() {
-            Some((self.patch, self.ratio?))
-        } else {
-            None
+        let (start, end) = (self.byte_count, self.byte_count + data.len());
+        let mut masked: Option<Vec<u8>> = None;
+
+        for (site, value) in self.sites.iter().zip(&mut self.values) {
+            let range = site.range();
+            let (from, to) = (range.start.max(start), range.end.min(end));
+            if from >= to {
+                continue;
+            }
+
+            value.extend_from_slice(&data[from - start..to - start]);
+            masked.get_or_insert_with(|| data.to_vec())[from - start..to - start]
+                .copy_from_slice(&site.original[from - range.start..to - range.start]);
         }
+
+        self.hasher.update(masked.as_deref().unwrap_or(data));
+        self.byte_count = end;
+    }
+
+    /// The hash and the current bytes of the sites, if all of them are found in the binary
+    pub fn finish(self) -> Option<([u8; 32], Vec<Vec<u8>>)> {
+        let complete = self
+            .sites
+            .iter()
+            .zip(&self.values)
+            .all(|(site, value)| value.len() == site.original.len());
+
+        complete.then(|| (self.hasher.finalize().into(), self.values))
     }
 }
 
-impl From<Box<dyn Patch>> for BinaryDetector {
-    fn from(from: Box<dyn Patch>) -> Self {
-        BinaryDetector {
-            patch: from,
-            hasher: Sha256::new(),
-            ratio: None,
-            byte_count: 0,
+/// Current bytes of each site by name
+struct SitesDebug<'a>(&'a Binary);
+
+impl Debug for SitesDebug<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sites = self.0.patch.sites().iter().zip(&self.0.values);
+        let sites = sites.filter(|(site, _)| site.owner.is_none());
+        f.debug_map()
+            .entries(sites.map(|(site, value)| (&site.name, crate::hex::encode(value))))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        hex,
+        ratio::{RATIO_HIGH_BYTE, RATIO_ORIGINAL},
+    };
+
+    pub(crate) const RATIO_OFFSET: usize = 0x810;
+    pub(crate) const FOV_OFFSET: usize = 0x81E;
+
+    /// A PE image with the original ratio at `.data+0x10` and FOV at `.data+0x1E` (across a read boundary)
+    pub(crate) fn binary() -> Vec<u8> {
+        let mut data = crate::pe::test::image();
+        data[RATIO_OFFSET..RATIO_OFFSET + 3].copy_from_slice(RATIO_ORIGINAL);
+        data[RATIO_OFFSET + 3] = RATIO_HIGH_BYTE;
+        data[FOV_OFFSET..FOV_OFFSET + 4].copy_from_slice(&1f32.to_le_bytes());
+        data
+    }
+
+    pub(crate) fn manifest() -> Manifest {
+        Manifest::parse(&manifest_toml()).unwrap()
+    }
+
+    pub(crate) fn manifest_toml() -> String {
+        format!(
+            r#"
+            [[version]]
+            name = "Test"
+            sha256 = "{}"
+
+            [[version.site]]
+            name = "ratio"
+            section = ".data"
+            offset = 0x10
+            original = "3A 46 71"
+
+            [[version.site]]
+            name = "fov"
+            section = ".data"
+            offset = 0x1E
+            original = "00 00 80 3F"
+            allowed = ["00 00 A0 3F"]
+            "#,
+            hex::encode(&Sha256::digest(binary()))
+        )
+    }
+
+    pub(crate) fn write_binary(dir: &Path, data: &[u8]) -> PathBuf {
+        let path = dir.join("eracer.exe");
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn binary_detect() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+
+        let binary = Binary::new(&path, &manifest()).unwrap();
+        assert_eq!(binary.patch.name(), "Test");
+        assert_eq!(binary.ratio_offset(), RATIO_OFFSET);
+        assert_eq!(binary.patch.sites()[1].offset, FOV_OFFSET);
+        assert_eq!(binary.aspect().known(), Some(Ratio::ORIGINAL));
+        assert_eq!(binary.timestamp, Some(0x3B0A_1C2D));
+        assert_eq!(binary.site_value(RATIO_SITE), Some(&RATIO_ORIGINAL[..]));
+        assert_eq!(binary.site_value("fov"), Some(&1f32.to_le_bytes()[..]));
+        assert_eq!(binary.site_value("hud"), None);
+
+        let mut other = crate::patch::test::binary();
+        other[0x900] ^= 0xFF;
+        let path = write_binary(dir.path(), &other);
+        assert!(matches!(
+            Binary::new(&path, &manifest()),
+            Err(ErrorCode::IncorrectHashOfBinary(_))
+        ));
+    }
+
+    /// A file with sites at the very beginning, in the middle (adjacent) and at the very end
+    fn detector_case() -> (Vec<u8>, items::PatchItem) {
+        let site = |name: &str, offset, original: &[u8]| Site {
+            name: name.to_owned(),
+            offset,
+            original: original.to_vec(),
+            allowed: vec![],
+            owner: None,
+        };
+        let sites = vec![
+            site("first", 0, &[0xA0, 0xA1]),
+            site("ratio", 5, RATIO_ORIGINAL),
+            site("next", 8, &[0xB0, 0xB1, 0xB2]),
+            site("last", 14, &[0xC0, 0xC1]),
+        ];
+
+        let mut original = (0..16).collect::<Vec<u8>>();
+        for site in &sites {
+            original[site.range()].copy_from_slice(&site.original);
+        }
+
+        let patch = items::PatchItem {
+            name: "Chunks".to_owned(),
+            sha2: Sha256::digest(&original).into(),
+            size: None,
+            sites,
+            blocks: None,
+        };
+
+        let mut data = original;
+        for site in patch.sites() {
+            data[site.range()]
+                .iter_mut()
+                .for_each(|byte| *byte = !*byte);
+        }
+
+        (data, patch)
+    }
+
+    #[test]
+    fn binary_detector_every_chunking() {
+        let (data, patch) = detector_case();
+        let expected = patch
+            .sites()
+            .iter()
+            .map(|site| data[site.range()].to_vec())
+            .collect::<Vec<_>>();
+
+        // every bit of `splits` is a possible boundary between two chunks
+        for splits in 0u32..1 << (data.len() - 1) {
+            let mut detector = BinaryDetector::new(patch.sites());
+            let mut start = 0;
+            for end in 1..=data.len() {
+                if end == data.len() || splits & (1 << (end - 1)) != 0 {
+                    detector.update(&data[start..end]);
+                    start = end;
+                }
+            }
+
+            let (sha2, values) = detector.finish().unwrap();
+            assert_eq!(&sha2, patch.sha2(), "{:b}", splits);
+            assert_eq!(values, expected, "{:b}", splits);
+        }
+    }
+
+    #[test]
+    fn binary_detector_chunk_sizes() {
+        let (data, patch) = detector_case();
+
+        /// Returns at most `size` bytes per read
+        struct Chunked<'a>(&'a [u8], usize);
+
+        impl Read for Chunked<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let count = self.0.len().min(self.1).min(buf.len());
+                buf[..count].copy_from_slice(&self.0[..count]);
+                self.0 = &self.0[count..];
+                Ok(count)
+            }
+        }
+
+        for size in 1..=data.len() + 1 {
+            let found = get_patch_by_binary(&mut Chunked(&data, size), &[&patch]);
+            assert_eq!(found.map(|(index, _)| index), Some(0), "{}", size);
+        }
+
+        // a truncated file never matches, even if a site is cut off
+        let mut detector = BinaryDetector::new(patch.sites());
+        detector.update(&data[..15]);
+        assert_eq!(detector.finish(), None);
+    }
+
+    #[test]
+    fn binary_detector_shared() {
+        let (data, patch) = detector_case();
+        let mut other = patch.clone();
+        other.sha2 = [0; 32];
+
+        let found = get_patch_by_binary(&mut &data[..], &[&other, &patch]);
+        assert_eq!(found.map(|(index, _)| index), Some(1));
+
+        // other sizes are skipped before hashing
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+        let size = format!("size = {}\n[[version.site]]", binary().len());
+        let manifest = Manifest::parse(&manifest_toml().replacen("[[version.site]]", &size, 1));
+        assert!(Binary::new(&path, &manifest.unwrap()).is_ok());
+
+        let size = format!("size = {}\n[[version.site]]", binary().len() + 1);
+        let manifest = Manifest::parse(&manifest_toml().replacen("[[version.site]]", &size, 1));
+        assert!(Binary::new(&path, &manifest.unwrap()).is_err());
+    }
+
+    /// `cargo test --release -- --ignored --nocapture binary_detector_bench`
+    #[test]
+    #[ignore]
+    fn binary_detector_bench() {
+        use std::time::Instant;
+
+        let data = (0..0x100000u32)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect::<Vec<_>>();
+        let manifest = Manifest::embedded();
+        let patches = manifest.patches(None);
+        let patches = patches.iter().map(|p| p.as_ref()).collect::<Vec<_>>();
+        let rounds = 20;
+
+        // the former loop: 32-byte reads, a hasher per version
+        let started = Instant::now();
+        for _ in 0..rounds {
+            let mut detectors = patches
+                .iter()
+                .map(|patch| BinaryDetector::new(patch.sites()))
+                .collect::<Vec<_>>();
+            for chunk in data.chunks(32) {
+                detectors.iter_mut().for_each(|d| d.update(chunk));
+            }
+            detectors
+                .into_iter()
+                .for_each(|d| assert!(d.finish().is_some()));
+        }
+        let before = started.elapsed() / rounds;
+
+        let started = Instant::now();
+        for _ in 0..rounds {
+            assert!(get_patch_by_binary(&mut &data[..], &patches).is_none());
         }
+        let after = started.elapsed() / rounds;
+
+        println!(
+            "32-byte reads: {:?}, {}-byte blocks: {:?}",
+            before, BLOCK_SIZE, after
+        );
+    }
+
+    #[test]
+    fn binary_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+
+        let binary = Binary::new(&path, &manifest()).unwrap();
+        assert!(binary.verify().is_ok());
+
+        let mut data = crate::patch::test::binary();
+        data[RATIO_OFFSET] = 0x00;
+        write_binary(dir.path(), &data);
+        assert!(matches!(
+            binary.verify(),
+            Err(ErrorCode::VerificationFailed(_, _))
+        ));
+
+        data[0x900] ^= 0xFF;
+        write_binary(dir.path(), &data);
+        assert!(matches!(
+            binary.verify(),
+            Err(ErrorCode::VerificationFailed(_, _))
+        ));
+    }
+
+    #[test]
+    fn binary_ratio_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut data = binary();
+        data[RATIO_OFFSET..RATIO_OFFSET + 3].copy_from_slice(&Ratio::new(5, 4).unwrap().hex());
+        let path = write_binary(dir.path(), &data);
+
+        let ratio = Ratio::new(16, 9).unwrap();
+        let binary = Binary::new(&path, &manifest()).unwrap();
+        let (source, target) = binary.ratio_patch(ratio).unwrap();
+        assert_eq!(source, crate::patch::test::binary());
+        assert_eq!(target[RATIO_OFFSET..RATIO_OFFSET + 3], ratio.hex());
+        assert_eq!(source[..RATIO_OFFSET], target[..RATIO_OFFSET]);
+        assert_eq!(source[RATIO_OFFSET + 3..], target[RATIO_OFFSET + 3..]);
+    }
+
+    #[test]
+    fn binary_set_site() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+        let fov = 1.25f32.to_le_bytes();
+
+        let mut binary = Binary::new(&path, &manifest()).unwrap();
+        binary.set_site("fov", &fov).unwrap();
+        assert_eq!(binary.site_value("fov"), Some(&fov[..]));
+        assert!(matches!(
+            binary.set_site("fov", &1.5f32.to_le_bytes()),
+            Err(ErrorCode::PatchFailed(_))
+        ));
+        assert!(binary.set_site("hud", &[0]).is_err());
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data[FOV_OFFSET..FOV_OFFSET + 4], fov);
+
+        // both sites are masked, so the version is still recognized
+        let ratio = Ratio::new(16, 9).unwrap();
+        binary.set_ratio(ratio).unwrap();
+        let binary = Binary::new(&path, &manifest()).unwrap();
+        assert_eq!(binary.site_value("fov"), Some(&fov[..]));
+        assert_eq!(binary.aspect().known(), Some(ratio));
+
+        let (source, _) = binary.ratio_patch(ratio).unwrap();
+        assert_eq!(source, crate::patch::test::binary());
+    }
+
+    #[test]
+    fn binary_mods() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = binary();
+        let toml = format!(
+            r#"{}
+            [[mod]]
+            name = "No-CD"
+
+            [[mod.region]]
+            offset = 0x900
+            original = "{}"
+            patched = "EB 05"
+
+            [[mod.region]]
+            offset = 0x980
+            original = "{}"
+            patched = "90"
+
+            [[mod.region]]
+            offset = 0x400
+            original = "{}"
+            patched = "90 90"
+            "#,
+            manifest_toml(),
+            hex::encode(&original[0x900..0x902]),
+            hex::encode(&original[0x980..0x981]),
+            hex::encode(&original[0x400..0x402])
+        );
+        let manifest = Manifest::parse(&toml).unwrap();
+
+        let mut data = original.clone();
+        data[0x900..0x902].copy_from_slice(&[0xEB, 0x05]);
+        let path = write_binary(dir.path(), &data);
+        let binary = Binary::new(&path, &manifest).unwrap();
+        assert_eq!(binary.mods(), ["No-CD (partially)"]);
+
+        // the last region is in `.text`, a code section
+        data[0x980] = 0x90;
+        data[0x400..0x402].copy_from_slice(&[0x90, 0x90]);
+        let path = write_binary(dir.path(), &data);
+        let mut binary = Binary::new(&path, &manifest).unwrap();
+        assert_eq!(binary.mods(), ["No-CD"]);
+        assert!(format!("{:?}", binary).contains("mods: [\"No-CD\"]"));
+        binary.verify_original().unwrap();
+
+        // the ratio of a modded binary can be changed, regions of the mod cannot
+        binary.set_ratio(Ratio::new(16, 9).unwrap()).unwrap();
+        assert!(binary.set_site("No-CD#1", &[0x75]).is_err());
+        assert!(binary.verify_original().is_err());
+
+        // an unknown value in a region is not a known mod
+        data[0x980] = 0xCC;
+        let path = write_binary(dir.path(), &data);
+        assert!(Binary::new(&path, &manifest).is_err());
+        assert!(Binary::new(&path, &crate::patch::test::manifest()).is_err());
+    }
+
+    #[test]
+    fn binary_set_ratio() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+
+        let ratio = Ratio::new(21, 9).unwrap();
+        let mut binary = Binary::new(&path, &manifest()).unwrap();
+        let sha2 = binary.set_ratio(ratio).unwrap();
+        assert_eq!(binary.aspect().known(), Some(ratio));
+        assert!(!temp_path(&path).exists());
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data[RATIO_OFFSET..RATIO_OFFSET + 3], ratio.hex());
+        assert_eq!(sha2, <[u8; 32]>::from(Sha256::digest(&data)));
+
+        let binary = Binary::new(&path, &manifest()).unwrap();
+        assert_eq!(binary.aspect().known(), Some(ratio));
+    }
+
+    #[test]
+    fn binary_replace_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+
+        let mut data = binary();
+        data[0x400] = 0xCC;
+        let sha2 = replace_file(&path, &data).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert_eq!(sha2, <[u8; 32]>::from(Sha256::digest(&data)));
+        assert!(!temp_path(&path).exists());
+        assert!(Binary::new(&path, &manifest()).is_err());
     }
 }
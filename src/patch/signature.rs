@@ -0,0 +1,250 @@
+use std::{fmt::Display, path::Path};
+
+use sha2::{Digest, Sha256};
+
+use super::{items::PatchItem, Binary, Site, RATIO_SITE};
+use crate::{
+    error_code::ErrorCode,
+    pe::PeImage,
+    ratio::{RATIO_HIGH_BYTE, RATIO_LENGTH, RATIO_ORIGINAL, RATIO_TABLE},
+};
+
+/// A byte pattern with wildcards (`??`) that contains the original ratio bytes. It is used to
+/// locate the ratio in binaries that are not described by the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pattern: Vec<Option<u8>>,
+    /// position of `RATIO_ORIGINAL` inside of the pattern
+    site: usize,
+    /// the ratio is matched only at offsets that are multiples of it
+    align: usize,
+}
+
+/// The ratio of every known release is 4-byte aligned (`0x0C7ED8`, `0x0CAEFC`), as compilers
+/// place `f32` constants
+const RATIO_ALIGN: usize = 4;
+
+impl Default for Signature {
+    /// The whole original `f32`: three ratio bytes followed by the high byte, at a 4-byte
+    /// aligned offset as in the known releases. The bytes around the constant differ between
+    /// builds, a pattern with them can be given via `--signature`.
+    fn default() -> Self {
+        let mut pattern = RATIO_ORIGINAL.map(Some).to_vec();
+        pattern.push(Some(RATIO_HIGH_BYTE));
+
+        Self {
+            pattern,
+            site: 0,
+            align: RATIO_ALIGN,
+        }
+    }
+}
+
+impl Signature {
+    /// Parses a pattern like `"?? 3A 46 71 3F ?? D8"`. Its context bytes place the ratio, so
+    /// it may be matched at any offset.
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        let pattern = s
+            .split_whitespace()
+            .map(|token| match token {
+                "?" | "??" => Ok(None),
+                _ if token.len() == 2 => u8::from_str_radix(token, 16).map(Some).map_err(|_| ()),
+                _ => Err(()),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "a pattern must consist of hex bytes and `??` separated by spaces")?;
+
+        let mut sites = pattern
+            .windows(RATIO_LENGTH)
+            .enumerate()
+            .filter(|(_, w)| w.iter().zip(RATIO_ORIGINAL).all(|(p, r)| *p == Some(*r)));
+
+        match (sites.next(), sites.next()) {
+            (Some((site, _)), None) => Ok(Self {
+                pattern,
+                site,
+                align: 1,
+            }),
+            (None, _) => Err("a pattern must contain the original aspect ratio bytes `3A 46 71`"),
+            (Some(_), Some(_)) => {
+                Err("a pattern must contain the original aspect ratio bytes once")
+            }
+        }
+    }
+
+    /// Returns offsets of the ratio for every match of the pattern
+    pub fn scan(&self, data: &[u8]) -> Vec<usize> {
+        self.scan_value(data, RATIO_ORIGINAL)
+    }
+
+    /// `scan` with the ratio bytes of the pattern replaced by `value`, e.g. to find the ratio
+    /// in a binary that has already been patched
+    pub fn scan_value(&self, data: &[u8], value: &[u8; RATIO_LENGTH]) -> Vec<usize> {
+        let mut pattern = self.pattern.clone();
+        for (p, b) in pattern[self.site..self.site + RATIO_LENGTH]
+            .iter_mut()
+            .zip(value)
+        {
+            *p = Some(*b);
+        }
+
+        data.windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| {
+                window
+                    .iter()
+                    .zip(&pattern)
+                    .all(|(b, p)| p.is_none_or(|p| p == *b))
+            })
+            .map(|(offset, _)| offset + self.site)
+            .filter(|offset| offset % self.align == 0)
+            .collect()
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tokens = self
+            .pattern
+            .iter()
+            .map(|p| p.map_or("??".to_owned(), |b| format!("{:02X}", b)))
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+impl Binary {
+    /// Fallback for binaries that are not described by the manifest: the ratio is located by
+    /// `signature` and the binary is accepted if and only if it is matched exactly once. Matches
+    /// outside of initialized data sections of a PE image are ignored. If the original ratio is
+    /// not found, the ratio may have been patched already: the pattern is tried with the bytes of
+    /// the preset ratios (see `Ratio::presets` and `RATIO_TABLE`) instead.
+    pub fn from_signature(path: &Path, signature: &Signature) -> Result<Self, ErrorCode> {
+        let data = std::fs::read(path).map_err(ErrorCode::IO)?;
+        let path_str = path.to_string_lossy().to_string();
+        let pe = PeImage::parse(&data).ok();
+
+        let scan = |value| {
+            let mut offsets = signature.scan_value(&data, value);
+            if let Some(ref pe) = pe {
+                offsets.retain(|&offset| pe.is_initialized_data(offset as u64, RATIO_LENGTH));
+            }
+            offsets
+        };
+
+        let mut offsets = scan(RATIO_ORIGINAL);
+        if offsets.is_empty() {
+            let values = RATIO_TABLE.iter().map(|(_, hex)| **hex).collect::<Vec<_>>();
+
+            offsets = values.iter().flat_map(scan).collect();
+            offsets.sort();
+        }
+
+        let ratio_offset = match offsets[..] {
+            [offset] => offset,
+            [] => Err(ErrorCode::SignatureNotFound(path_str))?,
+            ref offsets => Err(ErrorCode::SignatureAmbiguous(path_str, offsets.to_vec()))?,
+        };
+
+        let site = Site {
+            name: RATIO_SITE.to_owned(),
+            offset: ratio_offset,
+            original: RATIO_ORIGINAL.to_vec(),
+            allowed: vec![],
+            owner: None,
+        };
+        let values = vec![data[site.range()].to_vec()];
+
+        // hashes of versions are taken with all sites containing their original bytes
+        let mut original = data;
+        original[site.range()].copy_from_slice(&site.original);
+
+        let patch = PatchItem {
+            name: format!("Unknown (signature match at {:#010X})", ratio_offset),
+            sha2: Sha256::digest(&original).into(),
+            size: Some(original.len() as u64),
+            sites: vec![site],
+            blocks: None,
+        };
+
+        Self::with_patch(path, Box::new(patch), values, pe.map(|pe| pe.timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ratio::Ratio;
+
+    #[test]
+    fn signature_parse() {
+        let signature = Signature::parse("?? 3A 46 71 3F ?? D8").unwrap();
+        assert_eq!(signature.site, 1);
+        assert_eq!(signature.to_string(), "?? 3A 46 71 3F ?? D8");
+        assert_eq!(Signature::default().to_string(), "3A 46 71 3F");
+
+        assert!(Signature::parse("3A 46 70 3F").is_err());
+        assert!(Signature::parse("3A 46 71 3A 46 71").is_err());
+        assert!(Signature::parse("3A 46 71 3F XY").is_err());
+        assert!(Signature::parse("3A4671").is_err());
+    }
+
+    #[test]
+    fn signature_scan() {
+        let data = [
+            0x00, 0x3A, 0x46, 0x71, 0x3F, 0xD8, 0x3A, 0x46, 0x71, 0x3F, 0x00,
+        ];
+
+        // the default pattern is matched at aligned offsets only
+        assert!(Signature::default().scan(&data).is_empty());
+        let mut aligned = vec![0; 4];
+        aligned.extend_from_slice(&data[1..]);
+        assert_eq!(Signature::default().scan(&aligned), vec![4]);
+
+        assert_eq!(
+            Signature::parse("3A 46 71 3F D8").unwrap().scan(&data),
+            vec![1]
+        );
+        assert_eq!(
+            Signature::parse("?? 3A 46 71 3F ??").unwrap().scan(&data),
+            vec![1, 6]
+        );
+        assert_eq!(
+            Signature::parse("?? 3A 46 71 3F ?? ??")
+                .unwrap()
+                .scan(&data),
+            vec![1]
+        );
+        assert!(Signature::parse("FF 3A 46 71")
+            .unwrap()
+            .scan(&data)
+            .is_empty());
+    }
+
+    #[test]
+    fn signature_patched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eracer.exe");
+        let write = |ratio: Ratio| {
+            let mut data = vec![0; 0x100];
+            data[0x40..0x43].copy_from_slice(&ratio.hex());
+            data[0x43] = RATIO_HIGH_BYTE;
+            std::fs::write(&path, data).unwrap();
+        };
+
+        // a preset written over the original ratio is found again, so it can be reset
+        write(Ratio::new(16, 9).unwrap());
+        let mut binary = Binary::from_signature(&path, &Signature::default()).unwrap();
+        assert_eq!(binary.aspect().known(), Ratio::new(16, 9).ok());
+        binary.set_ratio(Ratio::ORIGINAL).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(&data[0x40..0x43], RATIO_ORIGINAL);
+
+        write(Ratio::new(32, 9).unwrap());
+        assert!(matches!(
+            Binary::from_signature(&path, &Signature::default()),
+            Err(ErrorCode::SignatureNotFound(_))
+        ));
+    }
+}
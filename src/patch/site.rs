@@ -0,0 +1,53 @@
+use std::ops::Range;
+
+/// A named range of the binary that can be patched (the ratio, FOV, HUD scale and so on)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Site {
+    pub name: String,
+    /// offset from the zero-byte of the file
+    pub offset: usize,
+    /// bytes of the original release
+    pub original: Vec<u8>,
+    /// values the site may be set to besides `original` (any value of the same length if empty)
+    pub allowed: Vec<Vec<u8>>,
+    /// name of the known mod this site is a region of; such sites are only masked while
+    /// detecting the version and are never written
+    pub owner: Option<String>,
+}
+
+impl Site {
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.original.len()
+    }
+
+    pub fn accepts(&self, value: &[u8]) -> bool {
+        value.len() == self.original.len()
+            && (self.allowed.is_empty()
+                || value == self.original
+                || self.allowed.iter().any(|a| a == value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn site_accepts() {
+        let mut site = Site {
+            name: "fov".to_owned(),
+            offset: 0x10,
+            original: vec![1, 2],
+            allowed: vec![],
+            owner: None,
+        };
+        assert_eq!(site.range(), 0x10..0x12);
+        assert!(site.accepts(&[3, 4]));
+        assert!(!site.accepts(&[3]));
+
+        site.allowed = vec![vec![5, 6]];
+        assert!(site.accepts(&[5, 6]));
+        assert!(site.accepts(&[1, 2]));
+        assert!(!site.accepts(&[3, 4]));
+    }
+}
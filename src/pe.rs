@@ -0,0 +1,241 @@
+use std::io::Read;
+
+/// Headers of a PE/COFF image are never larger than this in practice
+const MAX_HEADERS_LENGTH: u64 = 0x10000;
+
+const MZ_SIGNATURE: &[u8; 2] = b"MZ";
+const PE_SIGNATURE: &[u8; 4] = b"PE\0\0";
+const PE_POINTER_OFFSET: usize = 0x3C;
+const COFF_HEADER_LENGTH: usize = 20;
+const SECTION_HEADER_LENGTH: usize = 40;
+const PE32_MAGIC: u16 = 0x10B;
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+
+const IMAGE_SCN_CNT_INITIALIZED_DATA: u32 = 0x0000_0040;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    pub characteristics: u32,
+}
+
+impl Section {
+    pub fn is_initialized_data(&self) -> bool {
+        self.characteristics & IMAGE_SCN_CNT_INITIALIZED_DATA != 0
+    }
+
+    /// File offset of `offset` bytes from the beginning of the section
+    pub fn offset(&self, offset: u32) -> Option<u64> {
+        (offset < self.raw_size).then(|| self.raw_offset as u64 + offset as u64)
+    }
+
+    fn contains_rva(&self, rva: u32) -> bool {
+        rva >= self.virtual_address && rva - self.virtual_address < self.virtual_size.max(1)
+    }
+
+    fn contains_offset(&self, offset: u64) -> bool {
+        offset >= self.raw_offset as u64 && offset - (self.raw_offset as u64) < self.raw_size as u64
+    }
+}
+
+/// Headers of a PE/COFF image (`eracer.exe`) that are needed to map addresses from a
+/// disassembler to offsets in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeImage {
+    pub timestamp: u32,
+    pub image_base: u64,
+    pub sections: Vec<Section>,
+}
+
+impl PeImage {
+    pub fn read<R: Read>(reader: R) -> Result<Self, &'static str> {
+        let mut headers = vec![];
+        reader
+            .take(MAX_HEADERS_LENGTH)
+            .read_to_end(&mut headers)
+            .map_err(|_| "cannot read PE headers")?;
+
+        Self::parse(&headers)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        if data.get(..2) != Some(MZ_SIGNATURE) {
+            return Err("not an executable (no MZ signature)");
+        }
+
+        let pe = read_u32(data, PE_POINTER_OFFSET)? as usize;
+        if data.get(pe..pe + PE_SIGNATURE.len()) != Some(PE_SIGNATURE) {
+            return Err("not a PE executable (no PE signature)");
+        }
+
+        let coff = pe + PE_SIGNATURE.len();
+        let sections_count = read_u16(data, coff + 2)? as usize;
+        let timestamp = read_u32(data, coff + 4)?;
+        let optional_length = read_u16(data, coff + 16)? as usize;
+
+        let optional = coff + COFF_HEADER_LENGTH;
+        let image_base = match read_u16(data, optional)? {
+            PE32_MAGIC => read_u32(data, optional + 28)? as u64,
+            PE32_PLUS_MAGIC => read_u64(data, optional + 24)?,
+            _ => return Err("unknown type of PE optional header"),
+        };
+
+        let table = optional + optional_length;
+        let sections = (0..sections_count)
+            .map(|i| {
+                let header = table + i * SECTION_HEADER_LENGTH;
+                let name = data
+                    .get(header..header + 8)
+                    .ok_or("truncated PE section table")?;
+                let name = String::from_utf8_lossy(name)
+                    .trim_end_matches('\0')
+                    .to_owned();
+
+                Ok(Section {
+                    name,
+                    virtual_size: read_u32(data, header + 8)?,
+                    virtual_address: read_u32(data, header + 12)?,
+                    raw_size: read_u32(data, header + 16)?,
+                    raw_offset: read_u32(data, header + 20)?,
+                    characteristics: read_u32(data, header + 36)?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            timestamp,
+            image_base,
+            sections,
+        })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Section that contains `offset` of the file
+    pub fn section_by_offset(&self, offset: u64) -> Option<&Section> {
+        self.sections.iter().find(|s| s.contains_offset(offset))
+    }
+
+    /// Maps a relative virtual address to an offset in the file. Returns `None` if the address is
+    /// not backed by the file (e.g. it points to `.bss`).
+    pub fn rva_to_offset(&self, rva: u32) -> Option<u64> {
+        self.sections
+            .iter()
+            .find(|s| s.contains_rva(rva))
+            .and_then(|s| s.offset(rva - s.virtual_address))
+    }
+
+    /// Maps an absolute virtual address (as shown by a disassembler) to an offset in the file
+    pub fn va_to_offset(&self, va: u64) -> Option<u64> {
+        let rva = va.checked_sub(self.image_base)?;
+        self.rva_to_offset(rva.try_into().ok()?)
+    }
+
+    /// Checks that all `length` bytes at `offset` lie inside one section with initialized data
+    pub fn is_initialized_data(&self, offset: u64, length: usize) -> bool {
+        self.is_section_data(offset, length)
+            && self
+                .section_by_offset(offset)
+                .is_some_and(Section::is_initialized_data)
+    }
+
+    /// Checks that all `length` bytes at `offset` lie inside the raw data of one section, code
+    /// included
+    pub fn is_section_data(&self, offset: u64, length: usize) -> bool {
+        self.section_by_offset(offset)
+            .is_some_and(|s| s.contains_offset(offset + length.max(1) as u64 - 1))
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, &'static str> {
+    let bytes = data.get(offset..offset + 2).ok_or("truncated PE headers")?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, &'static str> {
+    let bytes = data.get(offset..offset + 4).ok_or("truncated PE headers")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, &'static str> {
+    let bytes = data.get(offset..offset + 8).ok_or("truncated PE headers")?;
+    let mut buffer = [0; 8];
+    buffer.copy_from_slice(bytes);
+    Ok(u64::from_le_bytes(buffer))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Builds a PE32 image with `.text` (code) at 0x400 and `.data` (initialized data) at 0x800
+    pub(crate) fn image() -> Vec<u8> {
+        let mut data = vec![0; 0xC00];
+        data[..2].copy_from_slice(MZ_SIGNATURE);
+        data[PE_POINTER_OFFSET..PE_POINTER_OFFSET + 4].copy_from_slice(&0x80u32.to_le_bytes());
+        data[0x80..0x84].copy_from_slice(PE_SIGNATURE);
+
+        let coff = 0x84;
+        data[coff + 2..coff + 4].copy_from_slice(&2u16.to_le_bytes());
+        data[coff + 4..coff + 8].copy_from_slice(&0x3B0A_1C2Du32.to_le_bytes());
+        data[coff + 16..coff + 18].copy_from_slice(&0xE0u16.to_le_bytes());
+
+        let optional = coff + COFF_HEADER_LENGTH;
+        data[optional..optional + 2].copy_from_slice(&PE32_MAGIC.to_le_bytes());
+        data[optional + 28..optional + 32].copy_from_slice(&0x0040_0000u32.to_le_bytes());
+
+        let sections: [(&[u8], u32, u32, u32); 2] = [
+            (b".text", 0x1000, 0x400, 0x6000_0020),
+            (b".data", 0x2000, 0x800, 0xC000_0040),
+        ];
+        for (i, (name, va, raw, characteristics)) in sections.iter().enumerate() {
+            let header = optional + 0xE0 + i * SECTION_HEADER_LENGTH;
+            data[header..header + name.len()].copy_from_slice(name);
+            data[header + 8..header + 12].copy_from_slice(&0x500u32.to_le_bytes());
+            data[header + 12..header + 16].copy_from_slice(&va.to_le_bytes());
+            data[header + 16..header + 20].copy_from_slice(&0x400u32.to_le_bytes());
+            data[header + 20..header + 24].copy_from_slice(&raw.to_le_bytes());
+            data[header + 36..header + 40].copy_from_slice(&characteristics.to_le_bytes());
+        }
+
+        data
+    }
+
+    #[test]
+    fn pe_parse() {
+        let pe = PeImage::read(&image()[..]).unwrap();
+        assert_eq!(pe.timestamp, 0x3B0A_1C2D);
+        assert_eq!(pe.image_base, 0x0040_0000);
+        assert_eq!(pe.sections.len(), 2);
+        assert_eq!(pe.sections[1].name, ".data");
+        assert!(!pe.sections[0].is_initialized_data());
+        assert!(pe.sections[1].is_initialized_data());
+
+        assert!(PeImage::parse(&image()[..0x100]).is_err());
+        assert!(PeImage::parse(&[0; 0x400]).is_err());
+    }
+
+    #[test]
+    fn pe_mapping() {
+        let pe = PeImage::parse(&image()).unwrap();
+        assert_eq!(pe.rva_to_offset(0x2010), Some(0x810));
+        assert_eq!(pe.va_to_offset(0x0040_2010), Some(0x810));
+        assert_eq!(pe.section(".data").unwrap().offset(0x10), Some(0x810));
+
+        // virtual only part of the section
+        assert_eq!(pe.rva_to_offset(0x2450), None);
+        assert_eq!(pe.rva_to_offset(0x3000), None);
+        assert_eq!(pe.va_to_offset(0x1000), None);
+
+        assert!(pe.is_initialized_data(0x810, 3));
+        assert!(!pe.is_initialized_data(0x410, 3));
+        assert!(!pe.is_initialized_data(0xBFF, 3));
+        assert!(!pe.is_initialized_data(0x100, 3));
+    }
+}
@@ -0,0 +1,293 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{delta, hex, patch::Binary, store::Value};
+
+/// A single modification that would be made by a run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Change {
+    Registry {
+        key: String,
+        value: String,
+        old: Option<u32>,
+        new: u32,
+    },
+    /// a value of any type, `old` and `new` as `get-value` prints them
+    Value {
+        key: String,
+        value: String,
+        old: Option<String>,
+        new: String,
+    },
+    Bytes {
+        path: PathBuf,
+        offset: usize,
+        old: String,
+        new: String,
+    },
+    /// the whole file is replaced, e.g. by its backup
+    File { path: PathBuf, source: PathBuf },
+}
+
+/// The set of changes of a run, collected instead of applying them (see `--dry-run`)
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    changes: Vec<Change>,
+    notes: Vec<String>,
+}
+
+impl Plan {
+    pub fn registry(&mut self, key: &str, value: &str, old: Option<u32>, new: u32) {
+        if old != Some(new) {
+            self.changes.push(Change::Registry {
+                key: key.to_owned(),
+                value: value.to_owned(),
+                old,
+                new,
+            });
+        }
+    }
+
+    pub fn value(&mut self, key: &str, name: &str, old: Option<&Value>, new: &Value) {
+        if old != Some(new) {
+            self.changes.push(Change::Value {
+                key: key.to_owned(),
+                value: name.to_owned(),
+                old: old.map(Value::to_string),
+                new: new.to_string(),
+            });
+        }
+    }
+
+    pub fn bytes(&mut self, binary: &Binary, new: &[u8]) {
+        let aspect = binary.aspect();
+        let old = aspect.hex();
+        if old != new {
+            self.changes.push(Change::Bytes {
+                path: binary.path().to_owned(),
+                offset: binary.ratio_offset(),
+                old: hex::encode(old),
+                new: hex::encode(new),
+            });
+        }
+    }
+
+    /// Every range of `path` where `target` differs from `source`, e.g. after applying a patch
+    pub fn diff(&mut self, path: &Path, source: &[u8], target: &[u8]) {
+        let common = source.len().min(target.len());
+        let mut runs = delta::diff_runs(&source[..common], &target[..common]);
+        if source.len() != target.len() {
+            runs.push((common, source.len().max(target.len()) - common));
+        }
+
+        for (offset, length) in runs {
+            let range = |data: &[u8]| {
+                let end = (offset + length).min(data.len());
+                hex::encode(&data[offset.min(end)..end])
+            };
+            self.changes.push(Change::Bytes {
+                path: path.to_owned(),
+                offset,
+                old: range(source),
+                new: range(target),
+            });
+        }
+    }
+
+    pub fn file(&mut self, path: &Path, source: &Path) {
+        self.changes.push(Change::File {
+            path: path.to_owned(),
+            source: source.to_owned(),
+        });
+    }
+
+    /// Something that prevents a change from being planned
+    pub fn note(&mut self, note: String) {
+        self.notes.push(note);
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a plan is always serializable")
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut rows = vec![["TARGET", "LOCATION", "OLD", "NEW"].map(str::to_owned)];
+        for change in &self.changes {
+            rows.push(match change {
+                Change::Registry {
+                    key,
+                    value,
+                    old,
+                    new,
+                } => [
+                    format!(r"HKCU\{}", key),
+                    value.clone(),
+                    old.map_or("-".to_owned(), |old| old.to_string()),
+                    new.to_string(),
+                ],
+                Change::Value {
+                    key,
+                    value,
+                    old,
+                    new,
+                } => [
+                    format!(r"HKCU\{}", key),
+                    value.clone(),
+                    old.clone().unwrap_or_else(|| "-".to_owned()),
+                    new.clone(),
+                ],
+                Change::Bytes {
+                    path,
+                    offset,
+                    old,
+                    new,
+                } => [
+                    path.to_string_lossy().to_string(),
+                    format!("{:#010X}", offset),
+                    spaced(old),
+                    spaced(new),
+                ],
+                Change::File { path, source } => [
+                    path.to_string_lossy().to_string(),
+                    "whole file".to_owned(),
+                    "-".to_owned(),
+                    source.to_string_lossy().to_string(),
+                ],
+            });
+        }
+
+        let mut widths = [0; 4];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut table = String::new();
+        for row in &rows {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>();
+            table.push_str(line.join("  ").trim_end());
+            table.push_str("\r\n");
+        }
+
+        if self.changes.is_empty() {
+            table = "Nothing to change.\r\n".to_owned();
+        }
+
+        for note in &self.notes {
+            table.push_str(&format!("Note: {}\r\n", note));
+        }
+
+        table
+    }
+}
+
+/// `"3a4671"` -> `"3A 46 71"`
+fn spaced(hex: &str) -> String {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|c| String::from_utf8_lossy(c).to_uppercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        patch::{test::*, Binary},
+        ratio::Ratio,
+    };
+
+    #[test]
+    fn plan_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+        let binary = Binary::new(&path, &manifest()).unwrap();
+
+        let mut plan = Plan::default();
+        plan.registry("eRacer", "PREFERRED WIDTH", Some(1024), 1920);
+        plan.registry("eRacer", "PREFERRED HEIGHT", Some(768), 768);
+        plan.bytes(&binary, &Ratio::new(16, 9).unwrap().hex());
+        plan.bytes(&binary, &Ratio::ORIGINAL.hex());
+        plan.file(&path, &dir.path().join("eracer.exe.bak"));
+        plan.value("eRacer", "Sound", None, &Value::Binary(vec![1, 2]));
+        plan.value(
+            "eRacer",
+            "Name",
+            Some(&Value::String("a".into())),
+            &Value::String("a".into()),
+        );
+
+        assert_eq!(plan.changes.len(), 4);
+        assert_eq!(
+            plan.changes[1],
+            Change::Bytes {
+                path,
+                offset: RATIO_OFFSET,
+                old: "3a4671".to_owned(),
+                new: hex::encode(&Ratio::new(16, 9).unwrap().hex()),
+            }
+        );
+
+        let table = plan.to_table();
+        assert!(table.contains("PREFERRED WIDTH  1024"));
+        assert!(table.contains("0x00000810"));
+        assert!(table.contains("3A 46 71"));
+        assert!(table.contains("whole file"));
+        assert!(table
+            .lines()
+            .any(|line| line.contains("Sound") && line.ends_with("-         [01, 02]")));
+
+        let json: serde_json::Value = serde_json::from_str(&plan.to_json()).unwrap();
+        assert_eq!(json["changes"][0]["kind"], "registry");
+        assert_eq!(json["changes"][0]["new"], 1920);
+        assert_eq!(json["changes"][1]["offset"], RATIO_OFFSET);
+        assert_eq!(json["changes"][2]["kind"], "file");
+        assert_eq!(json["changes"][3]["kind"], "value");
+        assert_eq!(json["changes"][3]["new"], "[01, 02]");
+    }
+
+    #[test]
+    fn plan_diff() {
+        let path = Path::new("eracer.exe");
+        let bytes = |offset: usize, old: &str, new: &str| Change::Bytes {
+            path: path.to_owned(),
+            offset,
+            old: old.to_owned(),
+            new: new.to_owned(),
+        };
+
+        let mut plan = Plan::default();
+        plan.diff(path, b"abcdef", b"abcdef");
+        assert!(plan.changes.is_empty());
+
+        plan.diff(path, b"abcdef", b"aXYdeZ!");
+        plan.diff(path, b"abcdef", b"abc");
+        assert_eq!(
+            plan.changes,
+            vec![
+                bytes(1, "6263", "5859"),
+                bytes(5, "66", "5a"),
+                bytes(6, "", "21"),
+                bytes(3, "646566", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_empty() {
+        let mut plan = Plan::default();
+        assert_eq!(plan.to_table(), "Nothing to change.\r\n");
+
+        plan.note("unknown binary".to_owned());
+        assert!(plan.to_table().ends_with("Note: unknown binary\r\n"));
+    }
+}
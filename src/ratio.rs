@@ -1,74 +1,119 @@
-use std::{fmt::Display, ops::Deref};
+use std::{
+    f64::consts::PI,
+    fmt::{Debug, Display},
+    ops::Deref,
+};
 
 pub const RATIO_LENGTH: usize = 3;
 pub const RATIO_ORIGINAL: &[u8; RATIO_LENGTH] = &[0x3A, 0x46, 0x71];
 
-#[derive(Debug, Default)]
-pub struct RatioIterator(usize);
-
-impl Iterator for RatioIterator {
-    type Item = Ratio;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0 += 1;
-        Some(match self.0 {
-            1 => Ratio::Original,
-            2 => Ratio::W5H4,
-            3 => Ratio::W25H16,
-            4 => Ratio::W16H10,
-            5 => Ratio::W15H9,
-            6 => Ratio::W16H9,
-            7 => Ratio::W21H9,
-            _ => return None,
-        })
-    }
+/// The most significant byte of the little-endian `f32` stored at the ratio patch site.
+/// It is never patched, so only values from `[0.5; 2.0)` can be encoded.
+pub const RATIO_HIGH_BYTE: u8 = 0x3F;
+
+/// The angle (in radians) used by the original binary, i.e. for a 4:3 display.
+const ANGLE_ORIGINAL: f64 = 0.3 * PI;
+
+/// Slope of the angle against the logarithm of the aspect ratio, fitted to `RATIO_TABLE`.
+const ANGLE_SLOPE: f64 = 0.755;
+
+/// Values that were hardcoded in the previous releases of the utility. They are still recognized
+/// when reading a binary and serve as test vectors for `Ratio::angle`.
+pub const RATIO_TABLE: [((u32, u32), &[u8; RATIO_LENGTH]); 7] = [
+    ((4, 3), RATIO_ORIGINAL),
+    ((5, 4), &[0x66, 0x66, 0x66]),
+    ((25, 16), &[0x3B, 0xDF, 0x87]),
+    ((16, 10), &[0x61, 0xE0, 0x89]),
+    ((15, 9), &[0xBA, 0x2C, 0x8E]),
+    ((16, 9), &[0xE3, 0xA5, 0x93]),
+    ((21, 9), &[0x29, 0x5C, 0xAF]),
+];
+
+/// The largest deviation (in radians) of the formula of `Ratio::angle` from `RATIO_TABLE`
+pub const MAX_FIT_DEVIATION: f32 = 0.0065;
+
+/// Ratios written with a greater `H` are not recognized when reading a binary
+const MAX_TERM: u32 = 100;
+
+fn angle_by_ratio(ratio: f64) -> f64 {
+    ANGLE_ORIGINAL + ANGLE_SLOPE * (ratio / (4.0 / 3.0)).ln()
+}
+
+/// Inverse of `angle_by_ratio`
+fn ratio_by_angle(angle: f64) -> f64 {
+    (4.0 / 3.0) * ((angle - ANGLE_ORIGINAL) / ANGLE_SLOPE).exp()
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Ratio {
-    Original,
-    W5H4,
-    W25H16,
-    W16H10,
-    W15H9,
-    W16H9,
-    W21H9,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ratio {
+    w: u32,
+    h: u32,
 }
 
 impl Ratio {
-    pub fn hex(&self) -> &[u8; RATIO_LENGTH] {
-        match self {
-            Ratio::Original => RATIO_ORIGINAL,
-            Ratio::W5H4 => &[0x66, 0x66, 0x66],
-            Ratio::W25H16 => &[0x3B, 0xDF, 0x87],
-            Ratio::W16H10 => &[0x61, 0xE0, 0x89],
-            Ratio::W15H9 => &[0xBA, 0x2C, 0x8E],
-            Ratio::W16H9 => &[0xE3, 0xA5, 0x93],
-            Ratio::W21H9 => &[0x29, 0x5C, 0xAF],
+    pub const ORIGINAL: Ratio = Ratio { w: 4, h: 3 };
+
+    pub fn new(w: u32, h: u32) -> Result<Self, &'static str> {
+        if w == 0 || h == 0 {
+            return Err("aspect ratio must not contain zero");
+        }
+
+        let ratio = Self { w, h };
+        let angle = ratio.angle();
+        if !(0.5..2.0).contains(&angle) {
+            return Err("aspect ratio is out of supported range");
         }
-    }
 
-    pub fn variants() -> RatioIterator {
-        RatioIterator::default()
+        Ok(ratio)
     }
 
-    pub fn value(&self) -> (u32, u32) {
-        match self {
-            Ratio::Original | Ratio::W5H4 => (5, 4),
-            Ratio::W25H16 => (25, 16),
-            Ratio::W16H10 => (16, 10),
-            Ratio::W15H9 => (15, 9),
-            Ratio::W16H9 => (16, 9),
-            Ratio::W21H9 => (21, 9),
+    /// Encoded value of the ratio: the angle (in radians) that the game expects at the patched
+    /// offset. Ratios of `RATIO_TABLE` (in any terms, e.g. 32:18) keep its exact values; any
+    /// other ratio is computed as
+    ///
+    /// ```text
+    /// angle = 0.3 * PI + 0.755 * ln((W / H) / (4 / 3))
+    /// ```
+    ///
+    /// where `0.3 * PI` is the value of the original binary (4:3) and the slope is the least
+    /// squares fit over `RATIO_TABLE`. The values of the table were picked by hand, the formula
+    /// stays within `MAX_FIT_DEVIATION` of them. Storing the angle loses nothing: the high byte
+    /// of the `f32` is always `RATIO_HIGH_BYTE`.
+    pub fn angle(&self) -> f32 {
+        match self.preset_hex() {
+            Some(hex) => Aspect::from(*hex).angle(),
+            None => angle_by_ratio(self.w as f64 / self.h as f64) as f32,
         }
     }
 
+    /// The bytes of `RATIO_TABLE` for the same ratio
+    fn preset_hex(&self) -> Option<&'static [u8; RATIO_LENGTH]> {
+        RATIO_TABLE
+            .iter()
+            .find(|&&((w, h), _)| self.w as u64 * h as u64 == w as u64 * self.h as u64)
+            .map(|&(_, hex)| hex)
+    }
+
+    pub fn hex(&self) -> [u8; RATIO_LENGTH] {
+        let bytes = self.angle().to_le_bytes();
+        debug_assert_eq!(bytes[RATIO_LENGTH], RATIO_HIGH_BYTE);
+
+        let mut hex = [0; RATIO_LENGTH];
+        hex.copy_from_slice(&bytes[..RATIO_LENGTH]);
+        hex
+    }
+
+    /// Commonly used aspect ratios
+    pub fn presets() -> impl Iterator<Item = Ratio> {
+        RATIO_TABLE.iter().map(|&((w, h), _)| Ratio { w, h })
+    }
+
     pub fn w(&self) -> u32 {
-        self.value().0
+        self.w
     }
 
     pub fn h(&self) -> u32 {
-        self.value().1
+        self.h
     }
 }
 
@@ -82,14 +127,90 @@ impl TryFrom<&[u8; 3]> for Ratio {
     type Error = &'static str;
 
     fn try_from(value: &[u8; 3]) -> Result<Self, Self::Error> {
-        Ratio::variants()
-            .find(|r| r.hex() == value)
+        RATIO_TABLE
+            .iter()
+            .find(|(_, hex)| *hex == value)
+            .map(|&((w, h), _)| Ratio { w, h })
+            .or_else(|| Ratio::presets().find(|r| &r.hex() == value))
+            .or_else(|| {
+                // any other `W:H` the utility could have written, by inverting the formula
+                let ratio = ratio_by_angle(Aspect::from(*value).angle() as f64);
+                (1..=MAX_TERM)
+                    .map(|h| ((ratio * h as f64).round() as u32, h))
+                    .filter_map(|(w, h)| Ratio::new(w, h).ok())
+                    .find(|r| &r.hex() == value)
+            })
             .ok_or("Incorrect value of aspect ratio.")
     }
 }
 
+/// Raw bytes of the ratio patch site as they were found in a binary. Unlike `Ratio` they are
+/// not limited to the values this utility writes, so binaries patched by other tools (or by hand)
+/// can be inspected as well.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Aspect([u8; RATIO_LENGTH]);
+
+impl Aspect {
+    pub fn hex(&self) -> &[u8; RATIO_LENGTH] {
+        &self.0
+    }
+
+    /// The encoded `f32` (see `Ratio::angle`)
+    pub fn angle(&self) -> f32 {
+        f32::from_le_bytes([self.0[0], self.0[1], self.0[2], RATIO_HIGH_BYTE])
+    }
+
+    /// Aspect ratio (`W / H`) implied by the encoded angle
+    pub fn ratio(&self) -> f64 {
+        ratio_by_angle(self.angle() as f64)
+    }
+
+    /// Approximate horizontal field of view in degrees
+    pub fn fov(&self) -> f32 {
+        self.angle().to_degrees()
+    }
+
+    /// The ratio the bytes were written for: a preset or any `W:H` with `H` up to 100, in the
+    /// lowest terms that give the same bytes. Returns `None` if the bytes were not written by this
+    /// utility.
+    pub fn known(&self) -> Option<Ratio> {
+        Ratio::try_from(&self.0).ok()
+    }
+}
+
+impl From<[u8; RATIO_LENGTH]> for Aspect {
+    fn from(from: [u8; RATIO_LENGTH]) -> Self {
+        Self(from)
+    }
+}
+
+impl Debug for Aspect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ratio = match self.known() {
+            Some(ratio) => ratio.to_string(),
+            None => format!("custom (~{:.3}:1)", self.ratio()),
+        };
+
+        f.debug_struct("Aspect")
+            .field("hex", &format_args!("{:02X?}", self.0))
+            .field("ratio", &format_args!("{}", ratio))
+            .field("angle", &self.angle())
+            .field("fov", &format_args!("~{:.1}°", self.fov()))
+            .finish()
+    }
+}
+
 // This is a wrapper to avoid a trait blanket TryFrom problem
 // https://github.com/rust-lang/rust/issues/50133
+/// Parses `W:H`:
+///
+/// ```
+/// use eracer_config::{Ratio, RatioStr};
+///
+/// let ratio: Ratio = RatioStr("16:9").try_into().unwrap();
+/// assert_eq!(ratio, Ratio::new(16, 9).unwrap());
+/// assert!(Ratio::try_from(RatioStr("16x9")).is_err());
+/// ```
 pub struct RatioStr<T: AsRef<str>>(pub T);
 
 impl<T: AsRef<str>> Deref for RatioStr<T> {
@@ -104,32 +225,109 @@ impl<T: AsRef<str>> TryFrom<RatioStr<T>> for Ratio {
     type Error = &'static str;
 
     fn try_from(value: RatioStr<T>) -> Result<Self, Self::Error> {
-        let mut width = 0;
-        let mut height = 0;
-
-        for (count, char) in value.as_ref().chars().enumerate() {
-            if char == ':' {
-                width = (&value.as_ref()[0..count])
-                    .parse()
-                    .map_err(|_| "parse `W` error")?;
-                height = (&value.as_ref()[count + 1..])
-                    .parse()
-                    .map_err(|_| "parse `H` error")?;
-                break;
-            }
+        let (w, h) = value.as_ref().split_once(':').ok_or("invalid format")?;
+        let w = w.parse().map_err(|_| "parse `W` error")?;
+        let h = h.parse().map_err(|_| "parse `H` error")?;
+
+        Ratio::new(w, h)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(hex: &[u8; RATIO_LENGTH]) -> f32 {
+        f32::from_le_bytes([hex[0], hex[1], hex[2], RATIO_HIGH_BYTE])
+    }
+
+    #[test]
+    fn ratio_formula_reproduces_table() {
+        for ((w, h), hex) in RATIO_TABLE {
+            let formula = angle_by_ratio(w as f64 / h as f64) as f32;
+            let diff = (formula - decode(hex)).abs();
+            assert!(
+                diff <= MAX_FIT_DEVIATION,
+                "{}:{}: {} vs {}",
+                w,
+                h,
+                formula,
+                decode(hex)
+            );
         }
+    }
 
-        if width == 8 && height == 5 {
-            width = 16;
-            height = 10;
+    #[test]
+    fn ratio_presets_are_exact() {
+        for ((w, h), hex) in RATIO_TABLE {
+            assert_eq!(&Ratio::new(w, h).unwrap().hex(), hex);
+            assert_eq!(&Ratio::new(w * 2, h * 2).unwrap().hex(), hex);
         }
+    }
 
-        for ratio in Ratio::variants() {
-            if ratio.w() == width && ratio.h() == height {
-                return Ok(ratio);
-            }
+    #[test]
+    fn ratio_encoding_is_exact() {
+        for (w, h) in [(4, 3), (16, 9), (32, 9), (43, 18), (5, 4)] {
+            let ratio = Ratio::new(w, h).unwrap();
+            assert_eq!(decode(&ratio.hex()), ratio.angle());
         }
+    }
+
+    #[test]
+    fn ratio_original() {
+        assert_eq!(&Ratio::ORIGINAL.hex(), RATIO_ORIGINAL);
+        assert_eq!(Ratio::try_from(RATIO_ORIGINAL), Ok(Ratio::ORIGINAL));
+    }
+
+    #[test]
+    fn ratio_from_hex() {
+        assert_eq!(
+            Ratio::try_from(&[0xE3, 0xA5, 0x93]),
+            Ok(Ratio { w: 16, h: 9 })
+        );
+        assert_eq!(
+            Ratio::try_from(&Ratio { w: 16, h: 9 }.hex()),
+            Ok(Ratio { w: 16, h: 9 })
+        );
+        assert!(Ratio::try_from(&[0x00, 0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn aspect_decode() {
+        let aspect = Aspect::from(*RATIO_ORIGINAL);
+        assert_eq!(aspect.known(), Some(Ratio::ORIGINAL));
+        assert!((aspect.fov() - 54.0).abs() < 0.001);
+        assert!((aspect.ratio() - 4.0 / 3.0).abs() < 0.001);
+
+        let aspect = Aspect::from(Ratio::new(32, 9).unwrap().hex());
+        assert_eq!(aspect.known(), Ratio::new(32, 9).ok());
+        let aspect = Aspect::from(Ratio::new(86, 36).unwrap().hex());
+        assert_eq!(aspect.known(), Ratio::new(43, 18).ok());
+
+        let mut hex = Ratio::new(32, 9).unwrap().hex();
+        hex[0] ^= 0x01;
+        let aspect = Aspect::from(hex);
+        assert_eq!(aspect.known(), None);
+        assert!((aspect.ratio() - 32.0 / 9.0).abs() < 0.001);
+        assert!(format!("{:?}", aspect).contains("custom"));
+    }
+
+    #[test]
+    fn ratio_from_str() {
+        assert_eq!(RatioStr("16:9").try_into(), Ok(Ratio { w: 16, h: 9 }));
+        assert_eq!(RatioStr("32:9").try_into(), Ok(Ratio { w: 32, h: 9 }));
+        assert_eq!(RatioStr("43:18").try_into(), Ok(Ratio { w: 43, h: 18 }));
+
+        let ratio: Result<Ratio, _> = RatioStr("16x9").try_into();
+        assert!(ratio.is_err());
+
+        let ratio: Result<Ratio, _> = RatioStr("0:9").try_into();
+        assert!(ratio.is_err());
+
+        let ratio: Result<Ratio, _> = RatioStr("1:9").try_into();
+        assert!(ratio.is_err());
 
-        Err("Incorrect value of aspect ratio arg.")
+        let ratio: Result<Ratio, _> = RatioStr("-16:9").try_into();
+        assert!(ratio.is_err());
     }
 }
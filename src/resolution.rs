@@ -1,5 +1,9 @@
 use std::{fmt::Display, ops::Deref};
 
+/// The game does not start with a smaller resolution
+pub const MIN_WIDTH: u32 = 640;
+pub const MIN_HEIGHT: u32 = 480;
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Resolution {
     pub width: u32,
@@ -12,7 +16,6 @@ impl From<(u32, u32)> for Resolution {
     }
 }
 
-#[allow(dead_code)]
 impl Resolution {
     pub fn new(width: u32, height: u32) -> Self {
         Self { width, height }
@@ -27,6 +30,15 @@ impl Display for Resolution {
 
 // This is a wrapper to avoid a trait blanket TryFrom problem
 // https://github.com/rust-lang/rust/issues/50133
+/// Parses `WxH`, at least 640x480:
+///
+/// ```
+/// use eracer_config::{Resolution, ResolutionStr};
+///
+/// let resolution: Resolution = ResolutionStr("1920x1200").try_into().unwrap();
+/// assert_eq!(resolution, Resolution::new(1920, 1200));
+/// assert!(Resolution::try_from(ResolutionStr("320x240")).is_err());
+/// ```
 pub struct ResolutionStr<T: AsRef<str>>(pub T);
 
 impl<T: AsRef<str>> Deref for ResolutionStr<T> {
@@ -41,12 +53,12 @@ impl<T: AsRef<str>> TryFrom<ResolutionStr<T>> for Resolution {
     type Error = &'static str;
 
     fn try_from(value: ResolutionStr<T>) -> Result<Self, Self::Error> {
-        let mut split = value.as_ref().split(|c| c == 'x' || c == 'X');
+        let mut split = value.as_ref().split(['x', 'X']);
         if let (Some(w), Some(h), None) = (split.next(), split.next(), split.next()) {
             if let (Ok(w), Ok(h)) = (w.parse(), h.parse()) {
-                return if w < 640 {
+                return if w < MIN_WIDTH {
                     Err("minimal width is 640")
-                } else if h < 480 {
+                } else if h < MIN_HEIGHT {
                     Err("minimal height is 480")
                 } else {
                     Ok((w, h).into())
@@ -0,0 +1,251 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error_code::ErrorCode,
+    patch::{Binary, Cache, Manifest},
+    plan::Plan,
+    ratio::Ratio,
+    resolution::Resolution,
+    store::{Store, Value},
+};
+
+pub const ENTRY_KEY: &str = r"Software\Rage Games Ltd\eRacer";
+pub const EXECUTABLE: &str = r"eracer.exe";
+pub const INSTALLDIR_KEY: &str = r"HOVAPPDATA";
+pub const RESOLUTION_WIDTH_KEY: &str = r"PREFERRED WIDTH";
+pub const RESOLUTION_HEIGHT_KEY: &str = r"PREFERRED HEIGHT";
+
+/// Settings of the game stored in the registry and the binary they point to
+pub struct Settings {
+    /// `None` if there is no registry backend
+    store: Option<Box<dyn Store>>,
+    /// `None` if there is no registry backend
+    registry_path: Option<PathBuf>,
+    override_path: Option<PathBuf>,
+    /// `None` if there is no registry backend
+    resolution: Option<Resolution>,
+    binary: Option<Binary>,
+}
+
+impl std::fmt::Debug for Settings {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.debug_struct("Settings")
+            .field("registry_path", &self.registry_path)
+            .field("override_path", &self.override_path)
+            .field("path", &self.path())
+            .field("resolution", &self.resolution)
+            .field("binary", &self.binary)
+            .finish()
+    }
+}
+
+impl Settings {
+    /// Reads the install dir and the resolution from `store` and detects the version of
+    /// `eracer.exe` (at `override_path` if it is set). Without a store (see `store::system`) only
+    /// the binary at `override_path` is available.
+    pub fn load(
+        store: Option<Box<dyn Store>>,
+        override_path: Option<PathBuf>,
+        manifest: &Manifest,
+        cache: &mut Cache,
+    ) -> Result<Self, ErrorCode> {
+        let (registry_path, resolution) = match store {
+            Some(ref store) => {
+                let (path, resolution) = read(store.as_ref())?;
+                (Some(path), Some(resolution))
+            }
+            None if override_path.is_some() => (None, None),
+            None => Err(ErrorCode::RegistryUnsupported)?,
+        };
+
+        let mut this = Self {
+            store,
+            registry_path,
+            override_path,
+            resolution,
+            binary: None,
+        };
+        this.binary = Binary::new_cached(this.path(), manifest, cache).ok();
+
+        Ok(this)
+    }
+
+    pub fn path(&self) -> &Path {
+        self.override_path
+            .as_deref()
+            .or(self.registry_path.as_deref())
+            .expect("either the registry or the override path is set")
+    }
+
+    /// The resolution from the registry (`None` if there is no registry backend)
+    pub fn resolution(&self) -> Option<Resolution> {
+        self.resolution
+    }
+
+    /// The binary if it is a known version (or it has been set by `Settings::set_binary`)
+    pub fn binary(&self) -> Option<&Binary> {
+        self.binary.as_ref()
+    }
+
+    pub fn binary_mut(&mut self) -> Option<&mut Binary> {
+        self.binary.as_mut()
+    }
+
+    /// Replaces the detected binary, e.g. by one found by signature or re-detected after a patch
+    pub fn set_binary(&mut self, binary: Option<Binary>) {
+        self.binary = binary;
+    }
+
+    pub fn set_resolution(&mut self, resolution: impl Into<Resolution>) -> Result<(), ErrorCode> {
+        let resolution: Resolution = resolution.into();
+        let store = self.store.as_mut().ok_or(ErrorCode::RegistryUnsupported)?;
+
+        let old = self.resolution.unwrap_or_default();
+        store.write(RESOLUTION_WIDTH_KEY, &Value::U32(resolution.width))?;
+        self.resolution = Some((resolution.width, old.height).into());
+
+        store.write(RESOLUTION_HEIGHT_KEY, &Value::U32(resolution.height))?;
+        self.resolution = Some(resolution);
+
+        Ok(())
+    }
+
+    /// Collects changes that `set_resolution` and `Binary::set_ratio` would make
+    pub fn plan(&self, resolution: Option<Resolution>, ratio: Option<Ratio>) -> Plan {
+        let mut plan = Plan::default();
+
+        if let Some(resolution) = resolution {
+            match self.store.is_some() {
+                true => {
+                    let (old, new) = (self.resolution, resolution);
+                    plan.registry(
+                        ENTRY_KEY,
+                        RESOLUTION_WIDTH_KEY,
+                        old.map(|r| r.width),
+                        new.width,
+                    );
+                    plan.registry(
+                        ENTRY_KEY,
+                        RESOLUTION_HEIGHT_KEY,
+                        old.map(|r| r.height),
+                        new.height,
+                    );
+                }
+                false => plan.note(ErrorCode::RegistryUnsupported.to_string()),
+            }
+        }
+
+        if let Some(ratio) = ratio {
+            match self.binary {
+                Some(ref binary) => plan.bytes(binary, &ratio.hex()),
+                None => plan.note(format!(
+                    "File not found or unknown version of the binary ({:?})",
+                    self.path()
+                )),
+            }
+        }
+
+        plan
+    }
+}
+
+/// The path to `eracer.exe` and the resolution
+fn read(store: &dyn Store) -> Result<(PathBuf, Resolution), ErrorCode> {
+    let registry_path = match store
+        .read(INSTALLDIR_KEY)?
+        .ok_or(ErrorCode::RegistryInstallDirNotFound)?
+    {
+        Value::String(installed_dir) => store.host_path(&installed_dir).join(EXECUTABLE),
+        _ => Err(ErrorCode::RegistryInstallDirIncorrectType)?,
+    };
+
+    let width = match store
+        .read(RESOLUTION_WIDTH_KEY)?
+        .ok_or(ErrorCode::RegistryResolutionWidthNotFound)?
+    {
+        Value::U32(width) => width,
+        _ => Err(ErrorCode::RegistryResolutionWidthIncorrectType)?,
+    };
+
+    let height = match store
+        .read(RESOLUTION_HEIGHT_KEY)?
+        .ok_or(ErrorCode::RegistryResolutionHeightNotFound)?
+    {
+        Value::U32(height) => height,
+        _ => Err(ErrorCode::RegistryResolutionHeightIncorrectType)?,
+    };
+
+    Ok((registry_path, (width, height).into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{patch::test::*, store::MemoryStore};
+
+    #[test]
+    fn settings_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+        let store = MemoryStore::new([
+            (
+                INSTALLDIR_KEY,
+                Value::String(dir.path().to_string_lossy().to_string()),
+            ),
+            (RESOLUTION_WIDTH_KEY, Value::U32(640)),
+            (RESOLUTION_HEIGHT_KEY, Value::U32(480)),
+        ]);
+        let mut cache = Cache::in_memory();
+
+        let mut settings =
+            Settings::load(Some(Box::new(store.clone())), None, &manifest(), &mut cache).unwrap();
+        assert_eq!(settings.path(), path);
+        assert_eq!(settings.resolution(), Some((640, 480).into()));
+        assert!(settings.binary().is_some());
+
+        let plan = settings.plan(Some((1920, 1080).into()), None);
+        assert_eq!(plan.to_table().matches("PREFERRED").count(), 2);
+
+        settings.set_resolution((1920, 1080)).unwrap();
+        assert_eq!(
+            store.read(RESOLUTION_WIDTH_KEY).unwrap(),
+            Some(Value::U32(1920))
+        );
+
+        store
+            .clone()
+            .write(RESOLUTION_HEIGHT_KEY, &Value::String("1080".to_owned()))
+            .unwrap();
+        assert!(matches!(
+            Settings::load(Some(Box::new(store)), None, &manifest(), &mut cache),
+            Err(ErrorCode::RegistryResolutionHeightIncorrectType)
+        ));
+    }
+
+    #[test]
+    fn settings_without_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_binary(dir.path(), &binary());
+        let manifest = manifest();
+        let mut cache = Cache::in_memory();
+
+        assert!(matches!(
+            Settings::load(None, None, &manifest, &mut cache),
+            Err(ErrorCode::RegistryUnsupported)
+        ));
+
+        let mut settings = Settings::load(None, Some(path.clone()), &manifest, &mut cache).unwrap();
+        assert_eq!(settings.path(), path);
+        assert_eq!(settings.resolution(), None);
+        assert!(settings.binary().is_some());
+        assert!(matches!(
+            settings.set_resolution((1920, 1080)),
+            Err(ErrorCode::RegistryUnsupported)
+        ));
+
+        let plan = settings.plan(Some((1920, 1080).into()), None);
+        assert!(plan
+            .to_table()
+            .contains("Windows registry is not available"));
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use super::{Store, Value, WineStore};
+use crate::settings::{EXECUTABLE, INSTALLDIR_KEY};
+
+/// `eracer.exe` is looked for this deep inside of `drive_c`
+const SEARCH_DEPTH: usize = 4;
+
+/// Steam installs: native, the Debian package and Flatpak
+const STEAM_ROOTS: [&str; 3] = [
+    ".local/share/Steam",
+    ".steam/steam",
+    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+];
+const LUTRIS_CONFIGS: [&str; 2] = [".config/lutris/games", ".local/share/lutris/games"];
+/// Lutris puts new prefixes here by default
+const LUTRIS_GAMES: &str = "Games";
+/// Bottles installs: native and Flatpak
+const BOTTLES_ROOTS: [&str; 2] = [
+    ".local/share/bottles/bottles",
+    ".var/app/com.usebottles.bottles/data/bottles/bottles",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Launcher {
+    Wine,
+    Steam,
+    Lutris,
+    Bottles,
+}
+
+/// A Wine prefix with e-Racer installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prefix {
+    pub path: PathBuf,
+    pub launcher: Launcher,
+    /// Steam app id, name of the Lutris game or of the bottle
+    pub name: Option<String>,
+    /// whether `user.reg` has the settings of the game
+    pub has_entry: bool,
+    /// `eracer.exe` found in the prefix
+    pub executables: Vec<PathBuf>,
+}
+
+impl Display for Prefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name {
+            Some(ref name) => writeln!(f, "{:?} ({:?}: {})", self.path, self.launcher, name)?,
+            None => writeln!(f, "{:?} ({:?})", self.path, self.launcher)?,
+        }
+
+        match self.has_entry {
+            true => writeln!(f, "  settings: found in user.reg")?,
+            false => writeln!(f, "  settings: not found (run the game once)")?,
+        }
+
+        for executable in &self.executables {
+            writeln!(f, "  {}: {:?}", EXECUTABLE, executable)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Prefixes of plain Wine (`wineprefix` and `~/.wine`), Steam (Proton), Lutris and Bottles that
+/// contain the settings of the game or `eracer.exe`
+pub fn discover(home: &Path, wineprefix: Option<&Path>) -> Vec<Prefix> {
+    let mut candidates: Vec<(PathBuf, Launcher, Option<String>)> = vec![];
+    let mut push = |path: PathBuf, launcher, name| candidates.push((path, launcher, name));
+
+    if let Some(prefix) = wineprefix {
+        push(prefix.to_owned(), Launcher::Wine, None);
+    }
+    push(home.join(".wine"), Launcher::Wine, None);
+
+    for library in steam_libraries(home) {
+        for (path, name) in subdirs(&library.join("steamapps").join("compatdata")) {
+            push(path.join("pfx"), Launcher::Steam, Some(name));
+        }
+    }
+
+    for config in LUTRIS_CONFIGS {
+        for (path, name) in lutris_prefixes(&home.join(config)) {
+            push(path, Launcher::Lutris, Some(name));
+        }
+    }
+    for (path, name) in subdirs(&home.join(LUTRIS_GAMES)) {
+        push(path, Launcher::Lutris, Some(name));
+    }
+
+    for root in BOTTLES_ROOTS {
+        for (path, name) in subdirs(&home.join(root)) {
+            push(path, Launcher::Bottles, Some(name));
+        }
+    }
+
+    let mut seen = vec![];
+    candidates
+        .into_iter()
+        .filter(|(path, _, _)| {
+            let canonical = path.canonicalize().ok();
+            let new = canonical.is_some() && !seen.contains(&canonical);
+            seen.push(canonical);
+            new
+        })
+        .filter_map(|(path, launcher, name)| inspect(path, launcher, name))
+        .collect()
+}
+
+/// `discover` for the current user
+pub fn discover_all() -> Vec<Prefix> {
+    let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+    match var("HOME") {
+        Some(home) => discover(
+            Path::new(&home),
+            var("WINEPREFIX").as_deref().map(Path::new),
+        ),
+        None => vec![],
+    }
+}
+
+/// The prefix if it has the settings of the game or `eracer.exe`
+fn inspect(path: PathBuf, launcher: Launcher, name: Option<String>) -> Option<Prefix> {
+    let store = WineStore::open(&path).ok();
+    let has_entry = store.as_ref().is_some_and(WineStore::has_entry);
+
+    // the installed one first
+    let mut executables = vec![];
+    if let Some(Ok(Some(Value::String(dir)))) = store.as_ref().map(|s| s.read(INSTALLDIR_KEY)) {
+        let installed = store.as_ref()?.host_path(&dir).join(EXECUTABLE);
+        if installed.is_file() {
+            executables.push(installed);
+        }
+    }
+    find_executables(&path.join("drive_c"), SEARCH_DEPTH, &mut executables);
+
+    match has_entry || !executables.is_empty() {
+        true => Some(Prefix {
+            path,
+            launcher,
+            name,
+            has_entry,
+            executables,
+        }),
+        false => None,
+    }
+}
+
+fn find_executables(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+
+        if is_dir && depth > 0 && name != "windows" {
+            find_executables(&path, depth - 1, found);
+        } else if !is_dir && name == EXECUTABLE && !found.contains(&path) {
+            found.push(path);
+        }
+    }
+}
+
+/// Directories inside of `dir` with their names, sorted by name
+fn subdirs(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut dirs = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .map(|e| (e.path(), e.file_name().to_string_lossy().to_string()))
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs
+}
+
+/// Steam roots and the libraries listed in their `steamapps/libraryfolders.vdf`
+fn steam_libraries(home: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![];
+    for root in STEAM_ROOTS.iter().map(|root| home.join(root)) {
+        let vdf = root.join("steamapps").join("libraryfolders.vdf");
+        libraries.push(root);
+
+        // `"path"    "/mnt/games/SteamLibrary"`
+        let content = std::fs::read_to_string(vdf).unwrap_or_default();
+        for line in content.lines() {
+            let tokens = line.split('"').filter(|t| !t.trim().is_empty());
+            if let [key, path] = tokens.collect::<Vec<_>>()[..] {
+                if key == "path" {
+                    libraries.push(PathBuf::from(path.replace("\\\\", "\\")));
+                }
+            }
+        }
+    }
+
+    libraries
+}
+
+/// `prefix: /home/user/Games/eracer` of Lutris game configs (`*.yml`) in `dir`
+fn lutris_prefixes(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut prefixes = vec![];
+    for (path, name) in files(dir, "yml") {
+        let content = std::fs::read_to_string(path).unwrap_or_default();
+        let prefix = content
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("prefix:"))
+            .map(|prefix| prefix.trim().trim_matches(['"', '\'']))
+            .find(|prefix| !prefix.is_empty());
+        if let Some(prefix) = prefix {
+            prefixes.push((PathBuf::from(prefix), name));
+        }
+    }
+
+    prefixes
+}
+
+/// Files with `extension` inside of `dir` with their names without the extension
+fn files(dir: &Path, extension: &str) -> Vec<(PathBuf, String)> {
+    let mut files = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.extension().is_some_and(|e| e == extension))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            Some((path, name))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const USER_REG: &str = r#"WINE REGISTRY Version 2
+
+[Software\\Rage Games Ltd\\eRacer] 1700000000
+"HOVAPPDATA"="C:\\Rage\\eRacer"
+"#;
+
+    fn prefix(path: &Path, user_reg: &str, exe: Option<&str>) {
+        std::fs::create_dir_all(path.join("drive_c")).unwrap();
+        std::fs::write(path.join("user.reg"), user_reg).unwrap();
+        if let Some(exe) = exe {
+            let exe = path.join("drive_c").join(exe);
+            std::fs::create_dir_all(exe.parent().unwrap()).unwrap();
+            std::fs::write(exe, b"MZ").unwrap();
+        }
+    }
+
+    #[test]
+    fn discover_prefixes() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        let empty = "WINE REGISTRY Version 2\n";
+
+        // a Steam library listed in `libraryfolders.vdf`
+        let steam = home.join(STEAM_ROOTS[0]).join("steamapps");
+        let library = dir.path().join("SteamLibrary");
+        std::fs::create_dir_all(&steam).unwrap();
+        std::fs::write(
+            steam.join("libraryfolders.vdf"),
+            format!(
+                "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                library.display()
+            ),
+        )
+        .unwrap();
+        let proton = library.join("steamapps/compatdata/2147483649/pfx");
+        prefix(&proton, USER_REG, Some("Rage/eRacer/eracer.exe"));
+        prefix(&steam.join("compatdata/10/pfx"), empty, None);
+
+        // a Lutris game with its prefix elsewhere, and a bottle with the game but no settings
+        let lutris = dir.path().join("lutris-prefix");
+        prefix(&lutris, empty, Some("Program Files/eRacer/ERACER.EXE"));
+        let config = home.join(LUTRIS_CONFIGS[0]);
+        std::fs::create_dir_all(&config).unwrap();
+        std::fs::write(
+            config.join("e-racer.yml"),
+            format!("game:\n  exe: eracer.exe\n  prefix: {}\n", lutris.display()),
+        )
+        .unwrap();
+        let bottle = home.join(BOTTLES_ROOTS[0]).join("eRacer");
+        prefix(&bottle, empty, Some("a/b/c/d/e/eracer.exe"));
+
+        let prefixes = discover(&home, Some(&proton));
+        assert_eq!(prefixes.len(), 2);
+
+        // `wineprefix` comes first and is not repeated as a Steam prefix
+        assert_eq!(prefixes[0].path, proton);
+        assert_eq!(prefixes[0].launcher, Launcher::Wine);
+        assert!(prefixes[0].has_entry);
+        assert_eq!(
+            prefixes[0].executables,
+            [proton.join("drive_c/Rage/eRacer/eracer.exe")]
+        );
+
+        assert_eq!(prefixes[1].launcher, Launcher::Lutris);
+        assert_eq!(prefixes[1].name.as_deref(), Some("e-racer"));
+        assert!(!prefixes[1].has_entry);
+        assert_eq!(prefixes[1].executables.len(), 1);
+
+        let prefixes = discover(&home, None);
+        assert_eq!(prefixes[0].launcher, Launcher::Steam);
+        assert_eq!(prefixes[0].name.as_deref(), Some("2147483649"));
+        assert!(prefixes[0]
+            .to_string()
+            .contains("settings: found in user.reg"));
+    }
+}
@@ -0,0 +1,41 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use super::{Store, Value};
+use crate::error_code::ErrorCode;
+
+/// Values kept in memory, e.g. for tests. Clones share the values, so a clone given to `Settings`
+/// shows what has been written.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    /// names and values by lowercase names
+    values: Rc<RefCell<BTreeMap<String, (String, Value)>>>,
+}
+
+impl MemoryStore {
+    pub fn new<'a>(values: impl IntoIterator<Item = (&'a str, Value)>) -> Self {
+        let mut this = Self::default();
+        for (name, value) in values {
+            let _ = this.write(name, &value);
+        }
+
+        this
+    }
+}
+
+impl Store for MemoryStore {
+    fn read(&self, name: &str) -> Result<Option<Value>, ErrorCode> {
+        let values = self.values.borrow();
+        Ok(values.get(&name.to_lowercase()).map(|(_, v)| v.clone()))
+    }
+
+    fn write(&mut self, name: &str, value: &Value) -> Result<(), ErrorCode> {
+        self.values
+            .borrow_mut()
+            .insert(name.to_lowercase(), (name.to_owned(), value.clone()));
+        Ok(())
+    }
+
+    fn values(&self) -> Result<Vec<(String, Value)>, ErrorCode> {
+        Ok(self.values.borrow().values().cloned().collect())
+    }
+}
@@ -0,0 +1,147 @@
+//! Backends the settings of the game are read from and written to: values under `ENTRY_KEY` of
+//! the current user's registry, wherever it is kept.
+
+mod discover;
+mod memory;
+pub mod regfile;
+#[cfg(all(windows, feature = "windows-registry"))]
+mod registry;
+pub mod schema;
+mod wine;
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+pub use discover::{discover, discover_all, Launcher, Prefix};
+pub use memory::MemoryStore;
+#[cfg(all(windows, feature = "windows-registry"))]
+pub use registry::RegistryStore;
+pub use wine::{default_prefix, UserReg, WineStore};
+
+use crate::error_code::ErrorCode;
+
+/// A typed value of the registry
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Value {
+    /// `REG_SZ`
+    String(String),
+    /// `REG_DWORD`
+    U32(u32),
+    /// `REG_BINARY`
+    Binary(Vec<u8>),
+    /// a value of another type (`REG_EXPAND_SZ`, `REG_MULTI_SZ`, `REG_QWORD` and so on) as its
+    /// type number and raw bytes, as `hex(N):` of `.reg` files
+    Other(u32, Vec<u8>),
+}
+
+/// Names of types of registry values by their numbers
+const KINDS: [&str; 12] = [
+    "REG_NONE",
+    "REG_SZ",
+    "REG_EXPAND_SZ",
+    "REG_BINARY",
+    "REG_DWORD",
+    "REG_DWORD_BIG_ENDIAN",
+    "REG_LINK",
+    "REG_MULTI_SZ",
+    "REG_RESOURCE_LIST",
+    "REG_FULL_RESOURCE_DESCRIPTOR",
+    "REG_RESOURCE_REQUIREMENTS_LIST",
+    "REG_QWORD",
+];
+pub(crate) const REG_EXPAND_SZ: u32 = 2;
+
+impl Value {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "REG_SZ",
+            Value::U32(_) => "REG_DWORD",
+            Value::Binary(_) => "REG_BINARY",
+            Value::Other(kind, _) => KINDS.get(*kind as usize).unwrap_or(&"REG_UNKNOWN"),
+        }
+    }
+}
+
+/// A string as the registry keeps it: UTF-16LE with a terminating zero
+pub(crate) fn encode_utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain([0])
+        .flat_map(|c| c.to_le_bytes())
+        .collect()
+}
+
+/// Inverse of `encode_utf16`, the string ends at the first zero
+pub(crate) fn decode_utf16(bytes: &[u8]) -> String {
+    let chars = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0)
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&chars)
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::U32(n) => write!(f, "{}", n),
+            Value::Binary(bytes) => write!(f, "{:02X?}", bytes),
+            Value::Other(REG_EXPAND_SZ, bytes) => write!(f, "{:?}", decode_utf16(bytes)),
+            Value::Other(_, bytes) => write!(f, "{:02X?}", bytes),
+        }
+    }
+}
+
+/// Values under `ENTRY_KEY` of a registry
+pub trait Store {
+    /// The value named `name` (case-insensitive), `Ok(None)` if there is no such value
+    fn read(&self, name: &str) -> Result<Option<Value>, ErrorCode>;
+
+    fn write(&mut self, name: &str, value: &Value) -> Result<(), ErrorCode>;
+
+    /// Writes all `values`. Backends that keep the registry in a file write it once, so nothing
+    /// is written if any of the values fails.
+    fn write_all(&mut self, values: &[(String, Value)]) -> Result<(), ErrorCode> {
+        values
+            .iter()
+            .try_for_each(|(name, value)| self.write(name, value))
+    }
+
+    /// All values with their names
+    fn values(&self) -> Result<Vec<(String, Value)>, ErrorCode>;
+
+    /// A path stored by the game (e.g. `HOVAPPDATA`) as a path on this machine
+    fn host_path(&self, path: &str) -> PathBuf {
+        PathBuf::from(path)
+    }
+}
+
+/// The registry of this machine, `None` if there is no backend for it (see the
+/// `windows-registry` feature)
+pub fn system() -> Option<Box<dyn Store>> {
+    #[cfg(all(windows, feature = "windows-registry"))]
+    return Some(Box::new(RegistryStore));
+
+    #[cfg(not(all(windows, feature = "windows-registry")))]
+    None
+}
+
+/// The Wine `prefix` if it is given, otherwise the registry of this machine or, if there is no
+/// backend for it, the default Wine prefix (see `default_prefix`) if the game is installed there
+pub fn locate(prefix: Option<&Path>) -> Result<Option<Box<dyn Store>>, ErrorCode> {
+    if let Some(prefix) = prefix {
+        return Ok(Some(Box::new(WineStore::open(prefix)?)));
+    }
+
+    if let Some(store) = system() {
+        return Ok(Some(store));
+    }
+
+    let store = default_prefix()
+        .and_then(|prefix| WineStore::open(&prefix).ok())
+        .filter(WineStore::has_entry);
+    Ok(store.map(|store| Box::new(store) as Box<dyn Store>))
+}
@@ -0,0 +1,374 @@
+//! `.reg` files of the Registry Editor with the values under `ENTRY_KEY`, to move the settings
+//! of the game between machines
+
+use super::{decode_utf16, schema::Schema, wine::parse_hex, Store, Value, REG_EXPAND_SZ};
+use crate::{error_code::ErrorCode, settings::ENTRY_KEY};
+
+const HEADER_5: &str = "Windows Registry Editor Version 5.00";
+const HEADER_4: &str = "REGEDIT4";
+const HIVES: [&str; 2] = ["HKEY_CURRENT_USER", "HKCU"];
+const REG_MULTI_SZ: u32 = 7;
+/// `hex:` lines are wrapped as the Registry Editor does it
+const LINE_WIDTH: usize = 76;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum RegFormat {
+    /// `Windows Registry Editor Version 5.00`, UTF-16LE with BOM
+    #[default]
+    Regedit5,
+    /// `REGEDIT4`, ASCII strings only
+    Regedit4,
+}
+
+/// The file with `values` as the key `HKEY_CURRENT_USER\<ENTRY_KEY>`
+pub fn export(values: &[(String, Value)], format: RegFormat) -> Result<Vec<u8>, String> {
+    let header = match format {
+        RegFormat::Regedit5 => HEADER_5,
+        RegFormat::Regedit4 => HEADER_4,
+    };
+
+    let mut lines = vec![
+        header.to_owned(),
+        String::new(),
+        format!("[{}\\{}]", HIVES[0], ENTRY_KEY),
+    ];
+    for (name, value) in values {
+        let name = match name.is_empty() {
+            true => "@=".to_owned(),
+            false => format!("{}=", quote(name)),
+        };
+        lines.push(format_value(name, value, format)?);
+    }
+    lines.push(String::new());
+
+    let content = lines
+        .iter()
+        .map(|line| format!("{}\r\n", line))
+        .collect::<String>();
+    match format {
+        RegFormat::Regedit5 => Ok([0xFF, 0xFE]
+            .into_iter()
+            .chain(content.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect()),
+        RegFormat::Regedit4 if content.is_ascii() => Ok(content.into_bytes()),
+        RegFormat::Regedit4 => Err("REGEDIT4 files can contain ASCII only".to_owned()),
+    }
+}
+
+/// Values of `ENTRY_KEY` in the file. Other keys and deletions of keys and values are rejected.
+pub fn import(bytes: &[u8]) -> Result<Vec<(String, Value)>, String> {
+    let content = decode(bytes)?;
+    let mut lines = join_lines(&content).into_iter();
+
+    let format = match lines.next().as_deref().map(str::trim) {
+        Some(HEADER_5) => RegFormat::Regedit5,
+        Some(HEADER_4) => RegFormat::Regedit4,
+        _ => return Err("not a registry file".to_owned()),
+    };
+    if format == RegFormat::Regedit4 && !content.is_ascii() {
+        return Err("REGEDIT4 files can contain ASCII only".to_owned());
+    }
+
+    let mut values = vec![];
+    let mut in_key = false;
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(key) = line.strip_prefix('[') {
+            let key = key
+                .strip_suffix(']')
+                .ok_or_else(|| format!("incorrect key `{}`", line))?;
+            if let Some(key) = key.strip_prefix('-') {
+                return Err(format!("deletion of the key `{}` is not supported", key));
+            }
+            if !is_entry_key(key) {
+                return Err(format!("the key `{}` is not the one of the game", key));
+            }
+            in_key = true;
+            continue;
+        }
+
+        if !in_key {
+            return Err(format!("a value outside of a key: `{}`", line));
+        }
+        let (name, value) =
+            parse_entry(line, format).ok_or_else(|| format!("incorrect value `{}`", line))?;
+        values.retain(|(n, _): &(String, Value)| !n.eq_ignore_ascii_case(&name));
+        values.push((name, value));
+    }
+
+    Ok(values)
+}
+
+/// Checks the known values (see `Schema`) and writes all of them to the store
+pub fn apply(
+    store: &mut dyn Store,
+    schema: &Schema,
+    values: &[(String, Value)],
+) -> Result<(), ErrorCode> {
+    for (name, value) in values {
+        schema.check(name, value)?;
+    }
+
+    store.write_all(values)
+}
+
+/// UTF-16LE with BOM or UTF-8 (with or without BOM)
+fn decode(bytes: &[u8]) -> Result<String, String> {
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let chars = rest
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        return String::from_utf16(&chars).map_err(|e| e.to_string());
+    }
+
+    let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+    std::str::from_utf8(bytes)
+        .map(str::to_owned)
+        .map_err(|_| "the file is neither UTF-16 nor UTF-8".to_owned())
+}
+
+/// Lines with continuations (a trailing `\`) joined
+fn join_lines(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut continued = false;
+    for line in content.lines() {
+        let line = line.trim_end();
+        let (line, next) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+
+        match lines.last_mut() {
+            Some(last) if continued => last.push_str(line.trim_start()),
+            _ => lines.push(line.to_owned()),
+        }
+        continued = next;
+    }
+
+    lines
+}
+
+/// `HKEY_CURRENT_USER\Software\Rage Games Ltd\eRacer` (or `HKCU\...`)
+fn is_entry_key(key: &str) -> bool {
+    key.split_once('\\').is_some_and(|(hive, path)| {
+        HIVES.iter().any(|h| h.eq_ignore_ascii_case(hive)) && path.eq_ignore_ascii_case(ENTRY_KEY)
+    })
+}
+
+/// `"name"=data`, `@=data` is the default value
+fn parse_entry(line: &str, format: RegFormat) -> Option<(String, Value)> {
+    let (name, rest) = match line.strip_prefix('@') {
+        Some(rest) => (String::new(), rest),
+        None => parse_string(line)?,
+    };
+    let data = rest.trim_start().strip_prefix('=')?.trim();
+
+    let value = if data.starts_with('"') {
+        match parse_string(data)? {
+            (s, "") => Value::String(s),
+            _ => return None,
+        }
+    } else if let Some(hex) = data.strip_prefix("dword:") {
+        Value::U32(u32::from_str_radix(hex, 16).ok()?)
+    } else if let Some(hex) = data.strip_prefix("hex:") {
+        Value::Binary(parse_hex(hex)?)
+    } else if let Some((kind, hex)) = data.strip_prefix("hex(").and_then(|d| d.split_once("):")) {
+        let kind = u32::from_str_radix(kind, 16).ok()?;
+        let bytes = parse_hex(hex)?;
+        // strings of REGEDIT4 are ANSI, only its ASCII part is the same on every machine
+        let ansi = matches!(kind, 1 | REG_EXPAND_SZ | REG_MULTI_SZ);
+        if ansi && format == RegFormat::Regedit4 && !bytes.is_ascii() {
+            return None;
+        }
+
+        match (kind, format) {
+            (1, RegFormat::Regedit4) => Value::String(
+                bytes
+                    .iter()
+                    .take_while(|&&b| b != 0)
+                    .map(|&b| b as char)
+                    .collect(),
+            ),
+            (1, RegFormat::Regedit5) => Value::String(decode_utf16(&bytes)),
+            (3, _) => Value::Binary(bytes),
+            (4, _) => Value::U32(u32::from_le_bytes(bytes[..].try_into().ok()?)),
+            (REG_EXPAND_SZ | REG_MULTI_SZ, RegFormat::Regedit4) => {
+                let bytes = bytes.iter().flat_map(|&b| [b, 0]).collect();
+                Value::Other(kind, bytes)
+            }
+            _ => Value::Other(kind, bytes),
+        }
+    } else {
+        // `-` deletes the value
+        return None;
+    };
+
+    Some((name, value))
+}
+
+/// A quoted string with `\\` and `\"` escaped, returns it and the rest of the line
+fn parse_string(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut result = String::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((result, &s[i + 2..])),
+            '\\' => result.push(chars.next()?.1),
+            c => result.push(c),
+        }
+    }
+
+    None
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The line of a value, `name` is `"name"=`
+fn format_value(name: String, value: &Value, format: RegFormat) -> Result<String, String> {
+    Ok(match value {
+        Value::String(s) => name + &quote(s),
+        Value::U32(n) => format!("{}dword:{:08x}", name, n),
+        Value::Binary(bytes) => format_hex(name + "hex:", bytes),
+        // strings of REGEDIT4 are ANSI
+        Value::Other(kind @ (REG_EXPAND_SZ | REG_MULTI_SZ), bytes)
+            if format == RegFormat::Regedit4 =>
+        {
+            let bytes = bytes
+                .chunks_exact(2)
+                .map(|c| match c {
+                    [b, 0] if b.is_ascii() => Ok(*b),
+                    _ => Err(format!("REGEDIT4 files can contain ASCII only: {}", value)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            format_hex(format!("{}hex({:x}):", name, kind), &bytes)
+        }
+        Value::Other(kind, bytes) => format_hex(format!("{}hex({:x}):", name, kind), bytes),
+    })
+}
+
+/// `"name"=hex:01,02,...` wrapped by `,\` and an indent of two spaces
+fn format_hex(mut result: String, bytes: &[u8]) -> String {
+    let mut width = result.len();
+    for (i, b) in bytes.iter().enumerate() {
+        result.push_str(&format!("{:02x}", b));
+        width += 2;
+        if i + 1 < bytes.len() {
+            result.push(',');
+            width += 1;
+            if width >= LINE_WIDTH {
+                result.push_str("\\\r\n  ");
+                width = 2;
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        settings::{INSTALLDIR_KEY, RESOLUTION_HEIGHT_KEY, RESOLUTION_WIDTH_KEY},
+        store::{encode_utf16, MemoryStore},
+    };
+
+    fn values() -> Vec<(String, Value)> {
+        vec![
+            (
+                INSTALLDIR_KEY.to_owned(),
+                Value::String(r#"C:\Games\"eRacer""#.to_owned()),
+            ),
+            (RESOLUTION_WIDTH_KEY.to_owned(), Value::U32(1024)),
+            (RESOLUTION_HEIGHT_KEY.to_owned(), Value::U32(768)),
+            ("Controls".to_owned(), Value::Binary((0..40).collect())),
+            (
+                "Path".to_owned(),
+                Value::Other(REG_EXPAND_SZ, encode_utf16("%SystemRoot%")),
+            ),
+        ]
+    }
+
+    #[test]
+    fn regfile_export_import() {
+        let exported = export(&values(), RegFormat::Regedit4).unwrap();
+        let text = String::from_utf8(exported.clone()).unwrap();
+        assert!(text.starts_with(
+            "REGEDIT4\r\n\r\n[HKEY_CURRENT_USER\\Software\\Rage Games Ltd\\eRacer]\r\n"
+        ));
+        assert!(text.contains("\"HOVAPPDATA\"=\"C:\\\\Games\\\\\\\"eRacer\\\"\"\r\n"));
+        assert!(text.contains("\"PREFERRED WIDTH\"=dword:00000400\r\n"));
+        assert!(text.contains("\"Controls\"=hex:00,01,02,03,04,05,06,07,08,09,0a,0b,0c,0d,0e,0f,10,11,12,13,14,\\\r\n  15,"));
+        assert!(text.contains("\"Path\"=hex(2):25,53,"));
+        assert_eq!(import(&exported).unwrap(), values());
+
+        let exported = export(&values(), RegFormat::Regedit5).unwrap();
+        assert!(exported.starts_with(&[0xFF, 0xFE, b'W', 0]));
+        assert_eq!(import(&exported).unwrap(), values());
+
+        let cyrillic = [("Name".to_owned(), Value::String("Ёж".to_owned()))];
+        assert!(export(&cyrillic, RegFormat::Regedit4).is_err());
+        let exported = export(&cyrillic, RegFormat::Regedit5).unwrap();
+        assert_eq!(import(&exported).unwrap(), cyrillic);
+    }
+
+    #[test]
+    fn regfile_import_rejected() {
+        let file = |body: &str| format!("{}\n\n{}\n", HEADER_5, body).into_bytes();
+        let key = "[HKCU\\Software\\Rage Games Ltd\\eRacer]";
+
+        assert_eq!(
+            import(&file(&format!("; comment\n{}\n@=dword:1\n", key))).unwrap(),
+            [(String::new(), Value::U32(1))]
+        );
+        assert!(import(b"\"a\"=dword:1").is_err());
+        assert!(import(&file("[HKEY_CURRENT_USER\\Software\\Wine]\n\"a\"=dword:1")).is_err());
+        assert!(import(&file(&format!("[-{}]", &key[1..key.len() - 1]))).is_err());
+        assert!(import(&file(&format!("{}\n\"a\"=-", key))).is_err());
+
+        // REGEDIT4 is ANSI, its non-ASCII characters depend on the code page of the machine
+        let ansi = |body: &str| format!("{}\n\n{}\n{}\n", HEADER_4, key, body).into_bytes();
+        assert!(import(&ansi("\"a\"=\"ok\"")).is_ok());
+        assert!(import(&ansi("\"a\"=\"Ёж\"")).is_err());
+        assert!(import(&ansi("\"a\"=hex(1):e9,00")).is_err());
+        assert!(import(&ansi("\"a\"=hex(2):41,e9,00")).is_err());
+        let mut latin1 = ansi("\"a\"=\"caf\"");
+        latin1.insert(latin1.len() - 2, 0xE9);
+        assert!(import(&latin1).unwrap_err().contains("UTF-8"));
+    }
+
+    #[test]
+    fn regfile_apply() {
+        let mut store = MemoryStore::new([(RESOLUTION_HEIGHT_KEY, Value::U32(600))]);
+        let value = |name: &str, value| vec![(name.to_owned(), value)];
+
+        for values in [
+            value(RESOLUTION_WIDTH_KEY, Value::U32(320)),
+            value(RESOLUTION_WIDTH_KEY, Value::String("800".to_owned())),
+            value(RESOLUTION_HEIGHT_KEY, Value::U32(240)),
+            value(INSTALLDIR_KEY, Value::U32(1)),
+        ] {
+            assert!(matches!(
+                apply(&mut store, &Schema::default(), &values),
+                Err(ErrorCode::RegistryValueIncorrect(_, _))
+            ));
+        }
+        assert_eq!(store.values().unwrap().len(), 1);
+
+        apply(&mut store, &Schema::default(), &values()).unwrap();
+        assert_eq!(
+            store.read("preferred height").unwrap(),
+            Some(Value::U32(768))
+        );
+        assert_eq!(store.values().unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,107 @@
+use registry::{Data, Hive, Security};
+
+use super::{decode_utf16, encode_utf16, Store, Value};
+use crate::{
+    error_code::ErrorCode,
+    settings::{ENTRY_KEY, RESOLUTION_HEIGHT_KEY, RESOLUTION_WIDTH_KEY},
+};
+
+const REG_NONE: u32 = 0;
+const REG_DWORD_BIG_ENDIAN: u32 = 5;
+const REG_MULTI_SZ: u32 = 7;
+const REG_QWORD: u32 = 11;
+
+/// `HKEY_CURRENT_USER` of the Windows registry
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegistryStore;
+
+fn to_value(data: &Data) -> Value {
+    match data {
+        Data::String(s) => Value::String(s.to_string_lossy()),
+        Data::U32(n) => Value::U32(*n),
+        Data::Binary(bytes) => Value::Binary(bytes.clone()),
+        Data::ExpandString(s) => {
+            Value::Other(super::REG_EXPAND_SZ, encode_utf16(&s.to_string_lossy()))
+        }
+        Data::MultiString(strings) => {
+            let mut bytes = vec![];
+            for s in strings {
+                bytes.extend(encode_utf16(&s.to_string_lossy()));
+            }
+            bytes.extend([0, 0]);
+            Value::Other(REG_MULTI_SZ, bytes)
+        }
+        Data::U32BE(n) => Value::Other(REG_DWORD_BIG_ENDIAN, n.to_be_bytes().to_vec()),
+        Data::U64(n) => Value::Other(REG_QWORD, n.to_le_bytes().to_vec()),
+        _ => Value::Other(REG_NONE, vec![]),
+    }
+}
+
+fn to_data(value: &Value) -> Option<Data> {
+    let data = match value {
+        Value::String(s) => Data::String(s.parse().ok()?),
+        Value::U32(n) => Data::U32(*n),
+        Value::Binary(bytes) => Data::Binary(bytes.clone()),
+        Value::Other(super::REG_EXPAND_SZ, bytes) => {
+            Data::ExpandString(decode_utf16(bytes).parse().ok()?)
+        }
+        Value::Other(REG_MULTI_SZ, bytes) => {
+            let chars = bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            let strings = chars
+                .split(|&c| c == 0)
+                .filter(|s| !s.is_empty())
+                .map(|s| String::from_utf16_lossy(s).parse().ok())
+                .collect::<Option<Vec<_>>>()?;
+            Data::MultiString(strings)
+        }
+        Value::Other(REG_DWORD_BIG_ENDIAN, bytes) => {
+            Data::U32BE(u32::from_be_bytes(bytes[..].try_into().ok()?))
+        }
+        Value::Other(REG_QWORD, bytes) => Data::U64(u64::from_le_bytes(bytes[..].try_into().ok()?)),
+        Value::Other(REG_NONE, _) => Data::None,
+        Value::Other(_, _) => return None,
+    };
+
+    Some(data)
+}
+
+impl Store for RegistryStore {
+    fn read(&self, name: &str) -> Result<Option<Value>, ErrorCode> {
+        let entry = Hive::CurrentUser
+            .open(ENTRY_KEY, Security::Read)
+            .map_err(|_| ErrorCode::RegistryEntryNotFound)?;
+
+        Ok(entry.value(name).ok().map(|data| to_value(&data)))
+    }
+
+    fn write(&mut self, name: &str, value: &Value) -> Result<(), ErrorCode> {
+        let failed = || match name {
+            RESOLUTION_WIDTH_KEY => ErrorCode::RegistryResolutionWidthChange,
+            RESOLUTION_HEIGHT_KEY => ErrorCode::RegistryResolutionHeightChange,
+            _ => ErrorCode::RegistryValueChange(name.to_owned()),
+        };
+
+        let data = to_data(value).ok_or_else(failed)?;
+        Hive::CurrentUser
+            .create(ENTRY_KEY, Security::Write)
+            .map_err(|_| failed())?
+            .set_value(name, &data)
+            .map_err(|_| failed())
+    }
+
+    fn values(&self) -> Result<Vec<(String, Value)>, ErrorCode> {
+        let entry = Hive::CurrentUser
+            .open(ENTRY_KEY, Security::Read)
+            .map_err(|_| ErrorCode::RegistryEntryNotFound)?;
+
+        let values = entry
+            .values()
+            .filter_map(|value| value.ok())
+            .map(|value| (value.name().to_string_lossy(), to_value(value.data())))
+            .collect();
+        Ok(values)
+    }
+}
@@ -0,0 +1,267 @@
+//! Values under `ENTRY_KEY` the utility knows the meaning of. Other values of the game are shown
+//! and changed as they are, by their registry types. More values can be described by `[[value]]`
+//! entries of a manifest (see `Manifest::schema`).
+
+use std::{borrow::Cow, fmt::Display, str::FromStr};
+
+use super::Value;
+use crate::{
+    error_code::ErrorCode,
+    hex,
+    resolution::{MIN_HEIGHT, MIN_WIDTH},
+    settings::{INSTALLDIR_KEY, RESOLUTION_HEIGHT_KEY, RESOLUTION_WIDTH_KEY},
+};
+
+/// Types of values that can be set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Kind {
+    /// `REG_SZ`
+    String,
+    /// `REG_DWORD`
+    U32,
+    /// `REG_BINARY`
+    Binary,
+}
+
+impl Kind {
+    pub fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(_) => Some(Kind::String),
+            Value::U32(_) => Some(Kind::U32),
+            Value::Binary(_) => Some(Kind::Binary),
+            Value::Other(_, _) => None,
+        }
+    }
+
+    /// `s` as a value of this type: a string as is, a decimal or `0x` hex number, hex bytes
+    pub fn parse(self, s: &str) -> Result<Value, String> {
+        match self {
+            Kind::String => Ok(Value::String(s.to_owned())),
+            Kind::U32 => match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => s.parse(),
+            }
+            .map(Value::U32)
+            .map_err(|_| format!("`{}` is not a DWORD number", s)),
+            Kind::Binary => hex::parse(s).map(Value::Binary),
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::String => write!(f, "REG_SZ"),
+            Kind::U32 => write!(f, "REG_DWORD"),
+            Kind::Binary => write!(f, "REG_BINARY"),
+        }
+    }
+}
+
+/// `REG_SZ`, `REG_DWORD`, `REG_BINARY`, case-insensitive and with `REG_` optional
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_ascii_uppercase();
+        match upper.strip_prefix("REG_").unwrap_or(&upper) {
+            "SZ" => Ok(Kind::String),
+            "DWORD" => Ok(Kind::U32),
+            "BINARY" => Ok(Kind::Binary),
+            _ => Err(format!(
+                "unknown type `{}`, expected REG_SZ, REG_DWORD or REG_BINARY",
+                s
+            )),
+        }
+    }
+}
+
+/// A value with a known meaning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Known {
+    pub name: Cow<'static, str>,
+    /// human-readable name
+    pub title: Cow<'static, str>,
+    pub kind: Kind,
+    /// allowed range of a `REG_DWORD`
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+
+/// Values the game is known to read. Names, types and ranges of its other values (detail, sound,
+/// controls) are not confirmed yet, they are described by manifests instead.
+pub const KNOWN: [Known; 3] = [
+    Known {
+        name: Cow::Borrowed(INSTALLDIR_KEY),
+        title: Cow::Borrowed("Installation directory"),
+        kind: Kind::String,
+        min: None,
+        max: None,
+    },
+    Known {
+        name: Cow::Borrowed(RESOLUTION_WIDTH_KEY),
+        title: Cow::Borrowed("Screen width"),
+        kind: Kind::U32,
+        min: Some(MIN_WIDTH),
+        max: None,
+    },
+    Known {
+        name: Cow::Borrowed(RESOLUTION_HEIGHT_KEY),
+        title: Cow::Borrowed("Screen height"),
+        kind: Kind::U32,
+        min: Some(MIN_HEIGHT),
+        max: None,
+    },
+];
+
+/// `KNOWN` values and the ones described by a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    values: Vec<Known>,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self {
+            values: KNOWN.to_vec(),
+        }
+    }
+}
+
+impl Schema {
+    /// Adds the description of a value. A value must be described once; a range is allowed for
+    /// `REG_DWORD` only.
+    pub fn push(&mut self, known: Known) -> Result<(), String> {
+        if self.known(&known.name).is_some() {
+            return Err(format!("value `{}` is already described", known.name));
+        }
+        if known.kind != Kind::U32 && (known.min.is_some() || known.max.is_some()) {
+            return Err(format!(
+                "value `{}`: `min` and `max` are allowed for REG_DWORD only",
+                known.name
+            ));
+        }
+        if let (Some(min), Some(max)) = (known.min, known.max) {
+            if min > max {
+                return Err(format!(
+                    "value `{}`: `min` is greater than `max`",
+                    known.name
+                ));
+            }
+        }
+
+        self.values.push(known);
+        Ok(())
+    }
+
+    pub fn values(&self) -> &[Known] {
+        &self.values
+    }
+
+    /// The known value named `name` (case-insensitive)
+    pub fn known(&self, name: &str) -> Option<&Known> {
+        self.values
+            .iter()
+            .find(|k| k.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Checks the type and the range of a known value, any other value is accepted
+    pub fn check(&self, name: &str, value: &Value) -> Result<(), ErrorCode> {
+        let known = match self.known(name) {
+            Some(known) => known,
+            None => return Ok(()),
+        };
+        let incorrect =
+            |reason: String| ErrorCode::RegistryValueIncorrect(known.name.to_string(), reason);
+
+        if Kind::of(value) != Some(known.kind) {
+            return Err(incorrect(format!(
+                "expected {}, given {}",
+                known.kind,
+                value.kind()
+            )));
+        }
+
+        if let Value::U32(n) = *value {
+            if known.min.is_some_and(|min| n < min) || known.max.is_some_and(|max| n > max) {
+                let bound = |b: Option<u32>| b.map_or(String::from("..."), |b| b.to_string());
+                return Err(incorrect(format!(
+                    "{} is out of range {}..{}",
+                    n,
+                    bound(known.min),
+                    bound(known.max)
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn schema_check() {
+        assert_eq!("reg_dword".parse(), Ok(Kind::U32));
+        assert_eq!("SZ".parse(), Ok(Kind::String));
+        assert!("REG_QWORD".parse::<Kind>().is_err());
+
+        assert_eq!(Kind::U32.parse("0x280"), Ok(Value::U32(640)));
+        assert_eq!(Kind::U32.parse("480"), Ok(Value::U32(480)));
+        assert!(Kind::U32.parse("-1").is_err());
+        assert_eq!(Kind::Binary.parse("01 ff"), Ok(Value::Binary(vec![1, 255])));
+
+        let schema = Schema::default();
+        assert_eq!(
+            schema.known("preferred width").unwrap().title,
+            "Screen width"
+        );
+        assert!(schema.check("PREFERRED WIDTH", &Value::U32(800)).is_ok());
+        assert!(matches!(
+            schema.check("preferred width", &Value::U32(320)),
+            Err(ErrorCode::RegistryValueIncorrect(name, _)) if name == "PREFERRED WIDTH"
+        ));
+        assert!(schema.check("HOVAPPDATA", &Value::U32(1)).is_err());
+        assert!(schema.check("Sound", &Value::Binary(vec![])).is_ok());
+    }
+
+    #[test]
+    fn schema_push() {
+        let detail = Known {
+            name: "Detail".into(),
+            title: "Level of detail".into(),
+            kind: Kind::U32,
+            min: Some(0),
+            max: Some(2),
+        };
+
+        let mut schema = Schema::default();
+        schema.push(detail.clone()).unwrap();
+        assert_eq!(schema.values().len(), KNOWN.len() + 1);
+        assert!(schema.check("detail", &Value::U32(2)).is_ok());
+        assert!(schema.check("detail", &Value::U32(3)).is_err());
+        assert!(schema.check("detail", &Value::String("2".into())).is_err());
+
+        assert!(schema.push(detail.clone()).is_err());
+        let width = Known {
+            name: "preferred width".into(),
+            ..detail.clone()
+        };
+        assert!(schema.push(width).is_err());
+        let string = Known {
+            name: "Name".into(),
+            kind: Kind::String,
+            ..detail.clone()
+        };
+        assert!(schema.push(string).is_err());
+        let reversed = Known {
+            name: "Other".into(),
+            min: Some(3),
+            ..detail
+        };
+        assert!(schema.push(reversed).is_err());
+    }
+}
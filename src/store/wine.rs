@@ -0,0 +1,542 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{decode_utf16, encode_utf16, Store, Value, REG_EXPAND_SZ};
+use crate::{atomic, error_code::ErrorCode, settings::ENTRY_KEY};
+
+const USER_REG: &str = "user.reg";
+
+/// Contents of `user.reg` of a Wine prefix (the `HKEY_CURRENT_USER` hive). Only lines of values
+/// that are written are changed, the rest of the file is kept as is, line endings included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserReg {
+    lines: Vec<String>,
+    /// `\r\n` if the file uses it, `\n` otherwise
+    newline: &'static str,
+    /// whether the last line ends with `newline`
+    trailing_newline: bool,
+}
+
+/// A value of a key: its lines (a long value is continued by a trailing `\`), name and data
+struct Entry {
+    lines: Range<usize>,
+    name: String,
+    value: Value,
+}
+
+impl UserReg {
+    pub fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(str::to_owned).collect(),
+            newline: match content.contains("\r\n") {
+                true => "\r\n",
+                false => "\n",
+            },
+            trailing_newline: content.is_empty() || content.ends_with('\n'),
+        }
+    }
+
+    /// The value named `name` of the key `key` (e.g. `ENTRY_KEY`), names are case-insensitive
+    pub fn value(&self, key: &str, name: &str) -> Option<Value> {
+        self.entries(key)?
+            .into_iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+            .map(|e| e.value)
+    }
+
+    /// Sets the value, the key is added to the end of the file if there is no such key
+    pub fn set_value(&mut self, key: &str, name: &str, value: &Value) {
+        let line = format!("{}={}", escape(name), format_value(value));
+
+        let section = match self.section(key) {
+            Some(section) => section,
+            None => {
+                let time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                if self.lines.last().is_some_and(|l| !l.is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines
+                    .push(format!("[{}] {}", key.replace('\\', "\\\\"), time));
+                self.lines.len()..self.lines.len()
+            }
+        };
+
+        let entries = self.entries(key).unwrap_or_default();
+        match entries.iter().find(|e| e.name.eq_ignore_ascii_case(name)) {
+            Some(entry) => {
+                self.lines.splice(entry.lines.clone(), [line]);
+            }
+            None => {
+                // after the last line of the key, before blank lines separating the next key
+                let end = section
+                    .clone()
+                    .rev()
+                    .find(|&i| !self.lines[i].is_empty())
+                    .map_or(section.start, |i| i + 1);
+                self.lines.insert(end, line);
+            }
+        }
+    }
+
+    /// Lines of the body of `key` (without its header)
+    fn section(&self, key: &str) -> Option<Range<usize>> {
+        let header = self
+            .lines
+            .iter()
+            .position(|line| parse_header(line).is_some_and(|k| k.eq_ignore_ascii_case(key)))?;
+
+        let end = self.lines[header + 1..]
+            .iter()
+            .position(|line| line.starts_with('['))
+            .map_or(self.lines.len(), |i| header + 1 + i);
+
+        Some(header + 1..end)
+    }
+
+    fn entries(&self, key: &str) -> Option<Vec<Entry>> {
+        let section = self.section(key)?;
+        let mut entries = vec![];
+        let mut index = section.start;
+
+        while index < section.end {
+            let start = index;
+            let mut line = self.lines[index].clone();
+            while line.ends_with('\\') && index + 1 < section.end {
+                line.pop();
+                index += 1;
+                line.push_str(self.lines[index].trim_start());
+            }
+            index += 1;
+
+            if let Some((name, value)) = parse_entry(&line) {
+                entries.push(Entry {
+                    lines: start..index,
+                    name,
+                    value,
+                });
+            }
+        }
+
+        Some(entries)
+    }
+}
+
+impl std::fmt::Display for UserReg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lines.join(self.newline))?;
+        match self.trailing_newline && !self.lines.is_empty() {
+            true => write!(f, "{}", self.newline),
+            false => Ok(()),
+        }
+    }
+}
+
+/// `[Software\\Rage Games Ltd\\eRacer] 1234567890` -> `Software\Rage Games Ltd\eRacer`
+fn parse_header(line: &str) -> Option<String> {
+    let rest = line.strip_prefix('[')?;
+    let end = rest.rfind(']')?;
+    Some(rest[..end].replace("\\\\", "\\"))
+}
+
+/// `"name"=data`, `@=data` is the default value
+fn parse_entry(line: &str) -> Option<(String, Value)> {
+    let (name, rest) = match line.strip_prefix('@') {
+        Some(rest) => (String::new(), rest),
+        None => parse_string(line)?,
+    };
+    let data = rest.strip_prefix('=')?;
+
+    let value = if let Some(hex) = data.strip_prefix("dword:") {
+        Value::U32(u32::from_str_radix(hex.trim(), 16).ok()?)
+    } else if let Some(hex) = data.strip_prefix("hex:") {
+        Value::Binary(parse_hex(hex)?)
+    } else if data.starts_with('"') {
+        Value::String(parse_string(data)?.0)
+    } else if let Some(s) = data.strip_prefix("str(2):") {
+        Value::Other(REG_EXPAND_SZ, encode_utf16(&parse_string(s)?.0))
+    } else if let Some((kind, hex)) = data.strip_prefix("hex(").and_then(|d| d.split_once("):")) {
+        Value::Other(u32::from_str_radix(kind, 16).ok()?, parse_hex(hex)?)
+    } else {
+        return None;
+    };
+
+    Some((name, value))
+}
+
+/// A quoted string with Wine escapes, returns it and the rest of the line
+fn parse_string(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut result = String::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((result, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => result.push('\n'),
+                'r' => result.push('\r'),
+                't' => result.push('\t'),
+                '0' => result.push('\0'),
+                'x' => {
+                    let rest = &s[i + 3..];
+                    let len = rest
+                        .chars()
+                        .take(4)
+                        .take_while(|c| c.is_ascii_hexdigit())
+                        .count();
+                    let code = u32::from_str_radix(&rest[..len], 16).ok()?;
+                    result.push(char::from_u32(code)?);
+                    for _ in 0..len {
+                        chars.next();
+                    }
+                }
+                c => result.push(c),
+            },
+            c => result.push(c),
+        }
+    }
+
+    None
+}
+
+pub(super) fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(|b| u8::from_str_radix(b, 16).ok())
+        .collect()
+}
+
+/// A quoted string as Wine writes it
+fn escape(s: &str) -> String {
+    let mut result = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (' '..='~').contains(&c) => result.push(c),
+            c => result.push_str(&format!("\\x{:04x}", c as u32)),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn format_value(value: &Value) -> String {
+    let hex = |bytes: &[u8]| {
+        let bytes = bytes.iter().map(|b| format!("{:02x}", b));
+        bytes.collect::<Vec<_>>().join(",")
+    };
+
+    match value {
+        Value::String(s) => escape(s),
+        Value::U32(n) => format!("dword:{:08x}", n),
+        Value::Binary(bytes) => format!("hex:{}", hex(bytes)),
+        Value::Other(REG_EXPAND_SZ, bytes) => format!("str(2):{}", escape(&decode_utf16(bytes))),
+        Value::Other(kind, bytes) => format!("hex({:x}):{}", kind, hex(bytes)),
+    }
+}
+
+/// `$WINEPREFIX` or `~/.wine`
+pub fn default_prefix() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+    var("WINEPREFIX")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| Path::new(&home).join(".wine")))
+}
+
+/// `ENTRY_KEY` in `user.reg` of a Wine prefix. Wine must not be running while the file is
+/// changed: `wineserver` overwrites it on exit.
+#[derive(Debug, Clone)]
+pub struct WineStore {
+    prefix: PathBuf,
+    reg: UserReg,
+}
+
+impl WineStore {
+    /// Reads `user.reg` of the prefix
+    pub fn open(prefix: &Path) -> Result<Self, ErrorCode> {
+        let content = std::fs::read_to_string(prefix.join(USER_REG))
+            .map_err(|_| ErrorCode::WinePrefixNotFound(prefix.to_string_lossy().to_string()))?;
+        Ok(Self {
+            prefix: prefix.to_owned(),
+            reg: UserReg::parse(&content),
+        })
+    }
+
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    /// Whether the game has stored its settings in the prefix
+    pub fn has_entry(&self) -> bool {
+        self.reg.section(ENTRY_KEY).is_some()
+    }
+}
+
+impl Store for WineStore {
+    fn read(&self, name: &str) -> Result<Option<Value>, ErrorCode> {
+        self.reg
+            .section(ENTRY_KEY)
+            .ok_or(ErrorCode::RegistryEntryNotFound)?;
+        Ok(self.reg.value(ENTRY_KEY, name))
+    }
+
+    fn values(&self) -> Result<Vec<(String, Value)>, ErrorCode> {
+        let entries = self
+            .reg
+            .entries(ENTRY_KEY)
+            .ok_or(ErrorCode::RegistryEntryNotFound)?;
+        Ok(entries.into_iter().map(|e| (e.name, e.value)).collect())
+    }
+
+    fn write(&mut self, name: &str, value: &Value) -> Result<(), ErrorCode> {
+        self.write_all(&[(name.to_owned(), value.clone())])
+    }
+
+    fn write_all(&mut self, values: &[(String, Value)]) -> Result<(), ErrorCode> {
+        let mut reg = self.reg.clone();
+        for (name, value) in values {
+            reg.set_value(ENTRY_KEY, name, value);
+        }
+
+        let path = self.prefix.join(USER_REG);
+        atomic::replace(
+            &path,
+            reg.to_string().as_bytes(),
+            ErrorCode::WinePrefixWrite,
+        )?;
+        self.reg = reg;
+        Ok(())
+    }
+
+    /// `C:\Games\eRacer` is `<prefix>/dosdevices/c:/Games/eRacer` (`<prefix>/drive_c/...` if
+    /// there are no `dosdevices`). Names are matched case-insensitively as Windows does.
+    fn host_path(&self, path: &str) -> PathBuf {
+        let letter = match path.as_bytes() {
+            [letter, b':', ..] if letter.is_ascii_alphabetic() => letter.to_ascii_lowercase(),
+            _ => return PathBuf::from(path),
+        };
+
+        let device = self
+            .prefix
+            .join("dosdevices")
+            .join(format!("{}:", letter as char));
+        let mut host = match device.exists() || letter != b'c' {
+            true => device,
+            false => self.prefix.join("drive_c"),
+        };
+
+        for name in path[2..].split(['\\', '/']).filter(|n| !n.is_empty()) {
+            let exact = host.join(name);
+            let found = match exact.exists() {
+                true => None,
+                false => std::fs::read_dir(&host).ok().and_then(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.file_name())
+                        .find(|n| n.to_string_lossy().eq_ignore_ascii_case(name))
+                }),
+            };
+            match found {
+                Some(found) => host.push(found),
+                None => host = exact,
+            }
+        }
+
+        host
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const USER_REG_CONTENT: &str = r#"WINE REGISTRY Version 2
+;; All keys relative to \\User\\S-1-5-21-0-0-0-1000
+
+#arch=win32
+
+[Software\\Rage Games Ltd\\eRacer] 1700000000
+#time=1da1b2c3d4e5f60
+"HOVAPPDATA"="C:\\Program Files\\Rage\\eRacer"
+"PREFERRED HEIGHT"=dword:000001e0
+"PREFERRED WIDTH"=dword:00000280
+"Controls"=hex:01,02,03,\
+  04,05
+"Path"=str(2):"%SystemRoot%"
+
+[Software\\Wine] 1700000000
+"Version"="win7"
+"#;
+
+    #[test]
+    fn user_reg_read() {
+        let reg = UserReg::parse(USER_REG_CONTENT);
+        assert_eq!(reg.to_string(), USER_REG_CONTENT);
+        let crlf = USER_REG_CONTENT.replace('\n', "\r\n");
+        assert_eq!(UserReg::parse(&crlf).to_string(), crlf);
+        let unterminated = USER_REG_CONTENT.trim_end();
+        assert_eq!(UserReg::parse(unterminated).to_string(), unterminated);
+
+        let value = |name| reg.value(ENTRY_KEY, name);
+        assert_eq!(
+            value("HOVAPPDATA"),
+            Some(Value::String(r"C:\Program Files\Rage\eRacer".to_owned()))
+        );
+        assert_eq!(value("preferred width"), Some(Value::U32(640)));
+        assert_eq!(value("Controls"), Some(Value::Binary(vec![1, 2, 3, 4, 5])));
+        assert_eq!(value("Path").unwrap().kind(), "REG_EXPAND_SZ");
+        assert_eq!(value("Path").unwrap().to_string(), "\"%SystemRoot%\"");
+        assert_eq!(value("Version"), None);
+        assert_eq!(
+            reg.value("Software\\Wine", "Version"),
+            Some(Value::String("win7".to_owned()))
+        );
+    }
+
+    #[test]
+    fn user_reg_write() {
+        let mut reg = UserReg::parse(USER_REG_CONTENT);
+        reg.set_value(ENTRY_KEY, "PREFERRED WIDTH", &Value::U32(1920));
+        reg.set_value(ENTRY_KEY, "Controls", &Value::Binary(vec![0xFF]));
+        reg.set_value(ENTRY_KEY, "Name", &Value::String("Ёж \"1\"".to_owned()));
+
+        let expected = USER_REG_CONTENT
+            .replace("dword:00000280", "dword:00000780")
+            .replace("hex:01,02,03,\\\n  04,05", "hex:ff")
+            .replace(
+                "%SystemRoot%\"\n",
+                "%SystemRoot%\"\n\"Name\"=\"\\x0401\\x0436 \\\"1\\\"\"\n",
+            );
+        assert_eq!(reg.to_string(), expected);
+        assert_eq!(
+            UserReg::parse(&expected).value(ENTRY_KEY, "name"),
+            Some(Value::String("Ёж \"1\"".to_owned()))
+        );
+
+        let mut reg = UserReg::parse("WINE REGISTRY Version 2\n");
+        reg.set_value(ENTRY_KEY, "PREFERRED WIDTH", &Value::U32(800));
+        assert_eq!(
+            reg.value(ENTRY_KEY, "PREFERRED WIDTH"),
+            Some(Value::U32(800))
+        );
+        assert!(reg
+            .to_string()
+            .contains("\n\n[Software\\\\Rage Games Ltd\\\\eRacer] "));
+    }
+
+    #[test]
+    fn wine_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(USER_REG);
+        assert!(matches!(
+            WineStore::open(dir.path()),
+            Err(ErrorCode::WinePrefixNotFound(_))
+        ));
+        std::fs::write(&path, "WINE REGISTRY Version 2\n").unwrap();
+
+        let mut store = WineStore::open(dir.path()).unwrap();
+        assert!(!store.has_entry());
+        assert!(matches!(
+            store.read("HOVAPPDATA"),
+            Err(ErrorCode::RegistryEntryNotFound)
+        ));
+
+        store.write("PREFERRED HEIGHT", &Value::U32(1080)).unwrap();
+        let store = WineStore::open(dir.path()).unwrap();
+        assert_eq!(
+            store.read("PREFERRED HEIGHT").unwrap(),
+            Some(Value::U32(1080))
+        );
+        assert_eq!(store.read("HOVAPPDATA").unwrap(), None);
+
+        // values of other types are kept as they are
+        std::fs::write(&path, USER_REG_CONTENT).unwrap();
+        let mut store = WineStore::open(dir.path()).unwrap();
+        let multi = Value::Other(7, vec![b'a', 0, 0, 0, 0, 0]);
+        store.write("Multi", &multi).unwrap();
+        let reg = std::fs::read_to_string(&path).unwrap();
+        assert!(reg.contains("\"Multi\"=hex(7):61,00,00,00,00,00\n"));
+        assert!(reg.contains("\"Path\"=str(2):\"%SystemRoot%\"\n"));
+
+        // several values are written at once
+        let both = [
+            ("PREFERRED WIDTH".to_owned(), Value::U32(1920)),
+            ("Multi".to_owned(), multi.clone()),
+        ];
+        store.write_all(&both).unwrap();
+        let reg = std::fs::read_to_string(&path).unwrap();
+        assert!(reg.contains("\"PREFERRED WIDTH\"=dword:00000780\n"));
+
+        let values = WineStore::open(dir.path()).unwrap().values().unwrap();
+        let names = values.iter().map(|(name, _)| name.as_str());
+        assert_eq!(
+            names.collect::<Vec<_>>(),
+            [
+                "HOVAPPDATA",
+                "PREFERRED HEIGHT",
+                "PREFERRED WIDTH",
+                "Controls",
+                "Path",
+                "Multi"
+            ]
+        );
+        assert_eq!(values[5].1, multi);
+
+        // line endings and permissions of the file are kept
+        std::fs::write(&path, USER_REG_CONTENT.replace('\n', "\r\n")).unwrap();
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        let mut store = WineStore::open(dir.path()).unwrap();
+        store.write("PREFERRED WIDTH", &Value::U32(800)).unwrap();
+        let reg = std::fs::read_to_string(&path).unwrap();
+        assert!(reg.contains("\"PREFERRED WIDTH\"=dword:00000320\r\n"));
+        assert!(!reg.replace("\r\n", "").contains('\n'));
+        assert!(std::fs::metadata(&path).unwrap().permissions().readonly());
+
+        // a failed write is an error of the prefix, not of patching
+        std::fs::create_dir(atomic::temp_path(&path)).unwrap();
+        assert!(matches!(
+            store.write("PREFERRED WIDTH", &Value::U32(640)),
+            Err(ErrorCode::WinePrefixWrite(_))
+        ));
+        assert_eq!(
+            store.read("PREFERRED WIDTH").unwrap(),
+            Some(Value::U32(800))
+        );
+    }
+
+    #[test]
+    fn wine_host_path() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(USER_REG), USER_REG_CONTENT).unwrap();
+        let game = dir
+            .path()
+            .join("drive_c")
+            .join("Program Files")
+            .join("Rage");
+        std::fs::create_dir_all(&game).unwrap();
+
+        let store = WineStore::open(dir.path()).unwrap();
+        assert_eq!(
+            store.host_path(r"C:\PROGRAM FILES\rage\eRacer"),
+            game.join("eRacer")
+        );
+        assert_eq!(
+            store.host_path(r"d:\eRacer"),
+            dir.path().join("dosdevices").join("d:").join("eRacer")
+        );
+        assert_eq!(store.host_path("/opt/eRacer"), PathBuf::from("/opt/eRacer"));
+    }
+}